@@ -0,0 +1,132 @@
+//! Android Unreal Plugin Language (UPL) configuration for a module.
+
+use std::path::{Path, PathBuf};
+
+/// A `<uses-permission>` entry in the AndroidManifest.
+#[derive(Debug, Clone, Copy)]
+pub struct AndroidPermission<'a> {
+    /// Fully qualified, e.g. `com.oculus.permission.HAND_TRACKING`. A name
+    /// without a package, e.g. `INTERNET`, is taken as `android.permission.<name>`.
+    pub name: &'a str,
+    /// Only request the permission up to this API level.
+    pub max_sdk_version: Option<u32>,
+}
+impl<'a> AndroidPermission<'a> {
+    pub const fn new(name: &'a str) -> Self {
+        Self { name, max_sdk_version: None }
+    }
+
+    pub(crate) fn to_element(self) -> String {
+        let mut out = if self.name.contains('.') {
+            format!("<addPermission android:name=\"{}\"", xml_escape(self.name))
+        } else {
+            format!("<addPermission android:name=\"android.permission.{}\"", xml_escape(self.name))
+        };
+        if let Some(version) = self.max_sdk_version {
+            out.push_str(&format!(" android:maxSdkVersion=\"{}\"", version));
+        }
+        out.push_str("/>");
+        out
+    }
+}
+
+/// A `<uses-feature>` entry in the AndroidManifest.
+#[derive(Debug, Clone, Copy)]
+pub struct AndroidFeature<'a> {
+    /// e.g. `android.hardware.camera`
+    pub name: &'a str,
+    pub required: bool,
+    /// Minimum feature version, e.g. `0x401000` for `android.hardware.vulkan.version`.
+    pub version: Option<u32>,
+}
+impl AndroidFeature<'_> {
+    pub(crate) fn to_element(self) -> String {
+        let mut out = format!("<addFeature android:name=\"{}\" android:required=\"{}\"", xml_escape(self.name), self.required);
+        if let Some(version) = self.version {
+            out.push_str(&format!(" android:version=\"{}\"", version));
+        }
+        out.push_str("/>");
+        out
+    }
+}
+
+/// Everything a module adds to the APK through its `BaseAPL.xml`.
+#[derive(Default)]
+pub struct AndroidConfig<'a> {
+    pub permissions: &'a [AndroidPermission<'a>],
+    pub features: &'a [AndroidFeature<'a>],
+    /// Lines appended to `proguard-project.txt`, e.g. `-keep class com.foo.** { *; }`.
+    pub proguard_additions: &'a [&'a str],
+    /// Gradle coordinates added as `implementation` dependencies.
+    pub gradle_dependencies: &'a [&'a str],
+    /// Raw Gradle appended to the app's `build.gradle`.
+    pub build_gradle_additions: &'a [&'a str],
+    /// `<meta-data>` `(android:name, android:value)` pairs under `<application>`.
+    pub application_meta_data: &'a [(&'a str, &'a str)],
+    /// `<meta-data>` pairs under the `GameActivity` `<activity>`.
+    pub activity_meta_data: &'a [(&'a str, &'a str)],
+    /// Java `import` lines for `GameActivity.java`.
+    pub game_activity_import_additions: &'a [&'a str],
+    /// Java members added to the `GameActivity` class.
+    pub game_activity_class_additions: &'a [&'a str],
+    /// `.java` files staged into `Android/Java/<package>` and compiled into
+    /// the APK, e.g. the Java half of a JNI bridge. The generated gradle
+    /// setup has no Kotlin support.
+    pub java_sources: &'a [&'a Path],
+    /// Name of an exported Rust function
+    /// `extern "C" fn(vm: *mut JavaVM, activity: jobject)` that the module
+    /// calls from `StartupModule` with the process' `JavaVM*` and the
+    /// `GameActivity` instance.
+    pub jni_bootstrap: Option<&'a str>,
+}
+
+/// Folder, relative to the module directory, Java sources are staged into.
+pub(crate) const JAVA_STAGE_DIR: &str = "Android/Java";
+
+/// Where a Java source goes under the source root, based on its
+/// `package` declaration.
+pub(crate) fn java_source_path(source: &Path, contents: &str) -> PathBuf {
+    let package = contents
+        .lines()
+        .map(str::trim)
+        .find_map(|l| l.strip_prefix("package "))
+        .map(|p| p.trim().trim_end_matches(';').trim())
+        .unwrap_or("");
+    let mut path = package.split('.').filter(|p| !p.is_empty()).collect::<PathBuf>();
+    path.push(source.file_name().unwrap_or_default());
+    path
+}
+
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permissions() {
+        assert_eq!(AndroidPermission::new("INTERNET").to_element(), "<addPermission android:name=\"android.permission.INTERNET\"/>");
+        let permission = AndroidPermission { max_sdk_version: Some(28), ..AndroidPermission::new("com.oculus.permission.HAND_TRACKING") };
+        assert_eq!(permission.to_element(), "<addPermission android:name=\"com.oculus.permission.HAND_TRACKING\" android:maxSdkVersion=\"28\"/>");
+    }
+
+    #[test]
+    fn java_sources_go_into_their_package() {
+        let source = Path::new("java/Bridge.java");
+        assert_eq!(java_source_path(source, "/* x */\npackage com.foo.bridge ;\nclass Bridge {}"), Path::new("com/foo/bridge/Bridge.java"));
+        assert_eq!(java_source_path(source, "class Bridge {}"), Path::new("Bridge.java"));
+    }
+}
@@ -0,0 +1,705 @@
+//! The `extern "C"` API of a Rust crate, read from its sources with `syn`,
+//! and the C++ header declaring it for Unreal.
+
+use super::{Error, Result};
+use quote::ToTokens;
+use serde::Serialize;
+use std::path::Path;
+
+/// A type that can cross the C ABI, spelled with Unreal's type names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CType {
+    Void,
+    Bool,
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Int64,
+    UInt64,
+    Float,
+    Double,
+    /// `c_long`, 32 bits on Windows and 64 bits elsewhere like C's `long`
+    Long,
+    /// `c_ulong`
+    ULong,
+    /// `c_char`
+    Char,
+    /// `usize`
+    Size,
+    /// `isize`
+    SSize,
+    /// A `#[repr(C)]` struct or enum of the crate, or an opaque type only
+    /// used behind pointers.
+    Named(String),
+    Ptr { ty: Box<CType>, is_const: bool },
+    Array(Box<CType>, usize),
+    /// Parameter names are empty when the Rust type leaves them out.
+    FnPtr { params: Vec<(String, CType)>, ret: Box<CType> },
+}
+impl CType {
+    /// A declaration of `name` with this type, e.g. `int32 (*name)(float)`.
+    /// `name` may be empty for an abstract declarator.
+    pub fn declare(&self, name: &str) -> String {
+        let spaced = |ty: String| if name.is_empty() { ty } else { format!("{} {}", ty, name) };
+        match self {
+            CType::Ptr { ty, is_const } => {
+                match (ty.as_ref(), is_const) {
+                    (CType::FnPtr { .. }, _) => ty.declare(&format!("*{}", name)),
+                    (CType::Array(..), _) => ty.declare(&format!("(*{})", name)),
+                    (CType::Ptr { .. }, true) => spaced(format!("{} const*", ty.declare(""))),
+                    (ty, true) => spaced(format!("const {}*", ty.declare(""))),
+                    (ty, false) => spaced(format!("{}*", ty.declare(""))),
+                }
+            }
+            CType::Array(ty, len) => ty.declare(&format!("{}[{}]", name, len)),
+            CType::FnPtr { params, ret } => {
+                let params = match params.as_slice() {
+                    [] => "void".to_string(),
+                    params => params.iter().map(|(name, ty)| ty.declare(name)).collect::<Vec<_>>().join(", "),
+                };
+                ret.declare(&format!("(*{})({})", name, params))
+            }
+            ty => spaced(ty.name().to_string()),
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            CType::Void => "void",
+            CType::Bool => "bool",
+            CType::Int8 => "int8",
+            CType::UInt8 => "uint8",
+            CType::Int16 => "int16",
+            CType::UInt16 => "uint16",
+            CType::Int32 => "int32",
+            CType::UInt32 => "uint32",
+            CType::Int64 => "int64",
+            CType::UInt64 => "uint64",
+            CType::Float => "float",
+            CType::Double => "double",
+            CType::Long => "long",
+            CType::ULong => "unsigned long",
+            CType::Char => "ANSICHAR",
+            CType::Size => "SIZE_T",
+            CType::SSize => "SSIZE_T",
+            CType::Named(name) => name,
+            CType::Ptr { .. } | CType::Array(..) | CType::FnPtr { .. } => "",
+        }
+    }
+
+    fn from_syn(ty: &syn::Type) -> Option<Self> {
+        Some(match ty {
+            syn::Type::Paren(p) => Self::from_syn(&p.elem)?,
+            syn::Type::Group(g) => Self::from_syn(&g.elem)?,
+            syn::Type::Tuple(t) if t.elems.is_empty() => CType::Void,
+            syn::Type::Ptr(p) => CType::Ptr { ty: Box::new(Self::from_syn(&p.elem)?), is_const: p.const_token.is_some() },
+            syn::Type::Reference(r) => CType::Ptr { ty: Box::new(Self::from_syn(&r.elem)?), is_const: r.mutability.is_none() },
+            syn::Type::Array(a) => match &a.len {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(len), .. }) => {
+                    CType::Array(Box::new(Self::from_syn(&a.elem)?), len.base10_parse().ok()?)
+                }
+                _ => return None,
+            },
+            syn::Type::BareFn(f) if is_c_abi(f.abi.as_ref()) && f.variadic.is_none() => CType::FnPtr {
+                params: f.inputs
+                    .iter()
+                    .map(|a| {
+                        let name = a.name.as_ref().map(|(n, _)| n.to_string()).filter(|n| n != "_").unwrap_or_default();
+                        Some((name, Self::from_syn(&a.ty)?))
+                    })
+                    .collect::<Option<_>>()?,
+                ret: Box::new(Self::from_return(&f.output)?),
+            },
+            syn::Type::Path(p) if p.qself.is_none() => {
+                let last = p.path.segments.last()?;
+                let ident = last.ident.to_string();
+                match &last.arguments {
+                    // nullable function pointer
+                    syn::PathArguments::AngleBracketed(args) if ident == "Option" => match args.args.first()? {
+                        syn::GenericArgument::Type(inner @ syn::Type::BareFn(_)) => Self::from_syn(inner)?,
+                        _ => return None,
+                    },
+                    syn::PathArguments::None => match ident.as_str() {
+                        "bool" => CType::Bool,
+                        "i8" | "c_schar" => CType::Int8,
+                        "u8" | "c_uchar" => CType::UInt8,
+                        "i16" | "c_short" => CType::Int16,
+                        "u16" | "c_ushort" => CType::UInt16,
+                        "i32" | "c_int" => CType::Int32,
+                        "u32" | "c_uint" => CType::UInt32,
+                        "c_long" => CType::Long,
+                        "c_ulong" => CType::ULong,
+                        "i64" | "c_longlong" => CType::Int64,
+                        "u64" | "c_ulonglong" => CType::UInt64,
+                        "f32" | "c_float" => CType::Float,
+                        "f64" | "c_double" => CType::Double,
+                        "c_char" => CType::Char,
+                        "usize" | "size_t" => CType::Size,
+                        "isize" | "ssize_t" => CType::SSize,
+                        "c_void" => CType::Void,
+                        // a fat pointer, not a C string
+                        "str" => return None,
+                        _ => CType::Named(ident),
+                    },
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        })
+    }
+
+    fn from_return(output: &syn::ReturnType) -> Option<Self> {
+        match output {
+            syn::ReturnType::Default => Some(CType::Void),
+            syn::ReturnType::Type(_, ty) => Self::from_syn(ty),
+        }
+    }
+
+    /// Named types this type holds by value, i.e. not behind a pointer.
+    fn by_value_names<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            CType::Named(name) => out.push(name),
+            CType::Array(ty, _) => ty.by_value_names(out),
+            CType::FnPtr { params, ret } => {
+                params.iter().for_each(|(_, p)| p.by_value_names(out));
+                ret.by_value_names(out);
+            }
+            _ => {}
+        }
+    }
+
+    /// Named types this type refers to.
+    fn names<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            CType::Named(name) => out.push(name),
+            CType::Ptr { ty, .. } | CType::Array(ty, _) => ty.names(out),
+            CType::FnPtr { params, ret } => {
+                params.iter().for_each(|(_, p)| p.names(out));
+                ret.names(out);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Given to templates as its declaration, e.g. `const ANSICHAR*`.
+impl Serialize for CType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.declare(""))
+    }
+}
+
+/// A `#[no_mangle] extern "C"` function.
+#[derive(Debug, Clone, Serialize)]
+pub struct CFunction {
+    pub name: String,
+    pub params: Vec<(String, CType)>,
+    pub ret: CType,
+    pub docs: Vec<String>,
+}
+
+/// A `#[repr(C)]` struct with named fields.
+#[derive(Debug, Clone, Serialize)]
+pub struct CStruct {
+    pub name: String,
+    pub fields: Vec<(String, CType)>,
+    pub docs: Vec<String>,
+}
+
+/// A fieldless enum with a `#[repr]`.
+#[derive(Debug, Clone, Serialize)]
+pub struct CEnum {
+    pub name: String,
+    /// The integer type from `#[repr(u8)]` etc., `Int32` for `#[repr(C)]`.
+    pub repr: CType,
+    pub variants: Vec<(String, Option<i64>)>,
+    pub docs: Vec<String>,
+}
+
+/// Everything a crate exports through the C ABI, in source order.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CApi {
+    pub functions: Vec<CFunction>,
+    pub structs: Vec<CStruct>,
+    pub enums: Vec<CEnum>,
+}
+impl CApi {
+    /// Reads every `.rs` file under `src` (a directory or a single file).
+    pub fn parse(src: &Path) -> Result<Self> {
+        let mut files = vec![];
+        collect_rs_files(src, &mut files)?;
+        files.sort();
+        let mut api = CApi::default();
+        for file in files {
+            let contents = std::fs::read_to_string(&file)?;
+            let parsed = syn::parse_file(&contents)?;
+            api.add_items(&parsed.items)?;
+        }
+        api.check_by_value_types()?;
+        Ok(api)
+    }
+
+    /// Types only known by name can only be passed behind pointers: C++
+    /// can't know the layout of anything but the crate's `#[repr(C)]` types.
+    fn check_by_value_types(&self) -> Result<()> {
+        let is_known = |name: &str| self.structs.iter().any(|s| s.name == name) || self.enums.iter().any(|e| e.name == name);
+        let check = |item: &str, what: &str, ty: &CType| {
+            let mut names = vec![];
+            ty.by_value_names(&mut names);
+            match names.into_iter().find(|n| !is_known(n)) {
+                Some(name) => Err(Error::UnsupportedType(format!("{}: {}: {} is not a #[repr(C)] type of the crate", item, what, name))),
+                None => Ok(()),
+            }
+        };
+        for f in &self.functions {
+            for (param, ty) in &f.params {
+                check(&f.name, param, ty)?;
+            }
+            check(&f.name, "return", &f.ret)?;
+        }
+        for s in &self.structs {
+            for (field, ty) in &s.fields {
+                check(&s.name, field, ty)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_items(&mut self, items: &[syn::Item]) -> Result<()> {
+        for item in items {
+            match item {
+                syn::Item::Fn(f) if is_exported(f) => self.functions.push(CFunction::from_syn(&f.sig, &f.attrs)?),
+                syn::Item::Struct(s) if reprs(&s.attrs).iter().any(|r| r == "C") => {
+                    let name = s.ident.to_string();
+                    let fields = match &s.fields {
+                        syn::Fields::Named(fields) => fields
+                            .named
+                            .iter()
+                            .map(|f| {
+                                let field = f.ident.as_ref().unwrap().to_string();
+                                CType::from_syn(&f.ty)
+                                    .map(|ty| (field.clone(), ty))
+                                    .ok_or_else(|| unsupported(&name, &field, &f.ty))
+                            })
+                            .collect::<Result<Vec<_>>>()?,
+                        syn::Fields::Unit => vec![],
+                        syn::Fields::Unnamed(_) => {
+                            return Err(Error::UnsupportedType(format!("{}: tuple structs are not supported", name)))
+                        }
+                    };
+                    self.structs.push(CStruct { name, fields, docs: docs(&s.attrs) });
+                }
+                syn::Item::Enum(e) if e.variants.iter().all(|v| v.fields.is_empty()) => {
+                    // an integer repr wins over C in `#[repr(C, u8)]`
+                    let reprs = reprs(&e.attrs);
+                    let repr = reprs
+                        .iter()
+                        .find_map(|r| match r.as_str() {
+                            "i8" | "u8" | "i16" | "u16" | "i32" | "u32" | "i64" | "u64" => {
+                                CType::from_syn(&syn::parse_str(r).ok()?)
+                            }
+                            _ => None,
+                        })
+                        .or_else(|| reprs.iter().any(|r| r == "C").then_some(CType::Int32));
+                    let Some(repr) = repr else { continue };
+                    let variants = e.variants
+                        .iter()
+                        .map(|v| match &v.discriminant {
+                            None => Ok((v.ident.to_string(), None)),
+                            Some((_, d)) => int_value(d)
+                                .map(|value| (v.ident.to_string(), Some(value)))
+                                .ok_or_else(|| Error::UnsupportedType(format!("{}::{}: discriminant must be an integer literal", e.ident, v.ident))),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    self.enums.push(CEnum { name: e.ident.to_string(), repr, variants, docs: docs(&e.attrs) });
+                }
+                syn::Item::Mod(syn::ItemMod { content: Some((_, items)), .. }) => self.add_items(items)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Types used by the API that the crate does not define as
+    /// `#[repr(C)]`, which C++ only sees behind pointers.
+    pub fn opaque_types(&self) -> Vec<&str> {
+        let mut names = vec![];
+        for f in &self.functions {
+            f.params.iter().for_each(|(_, ty)| ty.names(&mut names));
+            f.ret.names(&mut names);
+        }
+        for s in &self.structs {
+            s.fields.iter().for_each(|(_, ty)| ty.names(&mut names));
+        }
+        let mut out: Vec<&str> = vec![];
+        for name in names {
+            let known = self.structs.iter().any(|s| s.name == name) || self.enums.iter().any(|e| e.name == name);
+            if !known && !out.contains(&name) {
+                out.push(name);
+            }
+        }
+        out
+    }
+
+    /// Structs after the structs their fields contain by value.
+    pub(crate) fn structs_in_declaration_order(&self) -> Vec<&CStruct> {
+        fn by_value(ty: &CType) -> Option<&str> {
+            match ty {
+                CType::Named(name) => Some(name),
+                CType::Array(ty, _) => by_value(ty),
+                _ => None,
+            }
+        }
+        let mut out: Vec<&CStruct> = vec![];
+        let mut pending = self.structs.iter().collect::<Vec<_>>();
+        while !pending.is_empty() {
+            let ready = pending.iter().position(|s| {
+                s.fields.iter().filter_map(|(_, ty)| by_value(ty)).all(|dep| {
+                    out.iter().any(|o| o.name == dep) || !pending.iter().any(|p| p.name == dep)
+                })
+            });
+            // a cycle can't compile anyway, keep source order
+            out.push(pending.remove(ready.unwrap_or(0)));
+        }
+        out
+    }
+
+    /// The header declaring the API, for the module `module_name`.
+    pub fn header(&self, module_name: &str) -> String {
+        let mut out = vec![
+            format!("// The C API of the Rust library used by the {} module.", module_name),
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreTypes.h\"".to_string(),
+        ];
+        let opaque = self.opaque_types();
+        if !opaque.is_empty() {
+            out.push(String::new());
+            for name in opaque {
+                out.push(format!("struct {};", name));
+            }
+        }
+        for e in &self.enums {
+            out.push(String::new());
+            push_docs(&mut out, &e.docs, "");
+            out.push(format!("enum class {} : {} {{", e.name, e.repr.declare("")));
+            for (variant, value) in &e.variants {
+                match value {
+                    Some(value) => out.push(format!("    {} = {},", variant, value)),
+                    None => out.push(format!("    {},", variant)),
+                }
+            }
+            out.push("};".to_string());
+        }
+        for s in self.structs_in_declaration_order() {
+            out.push(String::new());
+            push_docs(&mut out, &s.docs, "");
+            out.push(format!("struct {} {{", s.name));
+            for (field, ty) in &s.fields {
+                out.push(format!("    {};", ty.declare(field)));
+            }
+            out.push("};".to_string());
+        }
+        if !self.functions.is_empty() {
+            // imported from the Rust library, so not <MODULE>_API: it is
+            // dllexport while the module itself is being built
+            out.push(String::new());
+            out.push("extern \"C\" {".to_string());
+            for f in &self.functions {
+                push_docs(&mut out, &f.docs, "");
+                out.push(format!("{};", f.declare()));
+            }
+            out.push("}".to_string());
+        }
+        out.push(String::new());
+        out.join("\n")
+    }
+}
+
+impl CFunction {
+    fn from_syn(sig: &syn::Signature, attrs: &[syn::Attribute]) -> Result<Self> {
+        let name = sig.ident.to_string();
+        let params = sig.inputs
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match arg {
+                syn::FnArg::Typed(arg) => {
+                    let param = match arg.pat.as_ref() {
+                        syn::Pat::Ident(p) => p.ident.to_string(),
+                        _ => format!("arg{}", i),
+                    };
+                    CType::from_syn(&arg.ty)
+                        .map(|ty| (param.clone(), ty))
+                        .ok_or_else(|| unsupported(&name, &param, &arg.ty))
+                }
+                syn::FnArg::Receiver(_) => Err(Error::UnsupportedType(format!("{}: self", name))),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let ret = match &sig.output {
+            syn::ReturnType::Type(_, ty) => CType::from_syn(ty).ok_or_else(|| unsupported(&name, "return", ty))?,
+            syn::ReturnType::Default => CType::Void,
+        };
+        Ok(CFunction { name, params, ret, docs: docs(attrs) })
+    }
+
+    /// The C declaration, without the trailing `;`.
+    pub fn declare(&self) -> String {
+        let params = match self.params.as_slice() {
+            [] => "void".to_string(),
+            params => params.iter().map(|(name, ty)| ty.declare(name)).collect::<Vec<_>>().join(", "),
+        };
+        self.ret.declare(&format!("{}({})", self.name, params))
+    }
+}
+
+fn collect_rs_files(path: &Path, out: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            collect_rs_files(&entry?.path(), out)?;
+        }
+    } else if path.extension().is_some_and(|e| e == "rs") {
+        out.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn is_c_abi(abi: Option<&syn::Abi>) -> bool {
+    abi.is_some_and(|abi| abi.name.as_ref().is_none_or(|n| n.value() == "C" || n.value() == "C-unwind"))
+}
+
+/// `#[no_mangle]` (or `#[unsafe(no_mangle)]`), non-generic and `extern "C"`.
+fn is_exported(f: &syn::ItemFn) -> bool {
+    let no_mangle = f.attrs.iter().any(|a| {
+        a.path().is_ident("no_mangle")
+            || (a.path().is_ident("unsafe") && a.parse_args::<syn::Ident>().is_ok_and(|i| i == "no_mangle"))
+    });
+    no_mangle && is_c_abi(f.sig.abi.as_ref()) && f.sig.generics.params.is_empty()
+}
+
+/// The arguments of every `#[repr(...)]`.
+fn reprs(attrs: &[syn::Attribute]) -> Vec<String> {
+    let mut out = vec![];
+    for attr in attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        let _ = attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                out.push(ident.to_string());
+            }
+            // skip arguments such as align(8)
+            if meta.input.peek(syn::token::Paren) {
+                let _content;
+                syn::parenthesized!(_content in meta.input);
+            }
+            Ok(())
+        });
+    }
+    out
+}
+
+/// The `///` lines of an item.
+fn docs(attrs: &[syn::Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|a| match &a.meta {
+            syn::Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(s), .. }) => Some(s.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+pub(crate) fn push_docs(out: &mut Vec<String>, docs: &[String], indent: &str) {
+    for line in docs {
+        out.push(format!("{}/// {}", indent, line).trim_end().to_string());
+    }
+}
+
+fn int_value(expr: &syn::Expr) -> Option<i64> {
+    match expr {
+        syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(i), .. }) => i.base10_parse().ok(),
+        syn::Expr::Unary(syn::ExprUnary { op: syn::UnOp::Neg(_), expr, .. }) => int_value(expr).map(|v| -v),
+        syn::Expr::Paren(p) => int_value(&p.expr),
+        _ => None,
+    }
+}
+
+fn unsupported(item: &str, what: &str, ty: &syn::Type) -> Error {
+    Error::UnsupportedType(format!("{}: {}: {}", item, what, ty.to_token_stream()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> Result<CApi> {
+        let mut api = CApi::default();
+        api.add_items(&syn::parse_file(src)?.items)?;
+        api.check_by_value_types()?;
+        Ok(api)
+    }
+
+    fn ty(src: &str) -> Option<CType> {
+        CType::from_syn(&syn::parse_str(src).unwrap())
+    }
+
+    fn named(name: &str) -> CType {
+        CType::Named(name.to_string())
+    }
+
+    fn ptr(ty: CType, is_const: bool) -> CType {
+        CType::Ptr { ty: Box::new(ty), is_const }
+    }
+
+    #[test]
+    fn scalars() {
+        let expect = [
+            ("bool", CType::Bool),
+            ("i8", CType::Int8),
+            ("u8", CType::UInt8),
+            ("c_short", CType::Int16),
+            ("u16", CType::UInt16),
+            ("std::ffi::c_int", CType::Int32),
+            ("c_uint", CType::UInt32),
+            ("c_long", CType::Long),
+            ("core::ffi::c_ulong", CType::ULong),
+            ("c_longlong", CType::Int64),
+            ("c_ulonglong", CType::UInt64),
+            ("f32", CType::Float),
+            ("c_double", CType::Double),
+            ("c_char", CType::Char),
+            ("usize", CType::Size),
+            ("isize", CType::SSize),
+            ("()", CType::Void),
+        ];
+        for (src, expect) in expect {
+            assert_eq!(ty(src), Some(expect), "{src}");
+        }
+    }
+
+    #[test]
+    fn pointers() {
+        assert_eq!(ty("*const c_char"), Some(ptr(CType::Char, true)));
+        assert_eq!(ty("*mut c_void"), Some(ptr(CType::Void, false)));
+        assert_eq!(ty("&mut Foo"), Some(ptr(named("Foo"), false)));
+        assert_eq!(ty("*const *mut u8"), Some(ptr(ptr(CType::UInt8, false), true)));
+        assert_eq!(ty("*mut [f32; 4]"), Some(ptr(CType::Array(Box::new(CType::Float), 4), false)));
+    }
+
+    #[test]
+    fn fn_pointers() {
+        let expect = CType::FnPtr { params: vec![("user".to_string(), ptr(CType::Void, false)), (String::new(), CType::Int32)], ret: Box::new(CType::Void) };
+        assert_eq!(ty("extern \"C\" fn(user: *mut c_void, _: i32)"), Some(expect.clone()));
+        assert_eq!(ty("Option<unsafe extern \"C\" fn(user: *mut c_void, i32)>"), Some(expect));
+        assert_eq!(ty("fn(i32)"), None);
+        assert_eq!(ty("extern \"C\" fn(i32, ...)"), None);
+        assert_eq!(ty("Option<i32>"), None);
+    }
+
+    #[test]
+    fn unsupported_types() {
+        for src in ["[u8; N]", "Vec<u8>", "(i32, i32)", "<T as Trait>::Out", "dyn Fn()"] {
+            assert_eq!(ty(src), None, "{src}");
+        }
+    }
+
+    #[test]
+    fn declarators() {
+        let fn_ptr = CType::FnPtr { params: vec![("x".to_string(), CType::Float)], ret: Box::new(CType::Int32) };
+        let expect = [
+            (CType::Int32, "int32 x"),
+            (CType::ULong, "unsigned long x"),
+            (ptr(CType::Char, true), "const ANSICHAR* x"),
+            (ptr(ptr(CType::Char, true), true), "const ANSICHAR* const* x"),
+            (ptr(ptr(CType::UInt8, false), false), "uint8** x"),
+            (CType::Array(Box::new(CType::Float), 3), "float x[3]"),
+            (CType::Array(Box::new(CType::Array(Box::new(CType::UInt8), 2)), 3), "uint8 x[3][2]"),
+            (ptr(CType::Array(Box::new(CType::Float), 4), false), "float (*x)[4]"),
+            (fn_ptr.clone(), "int32 (*x)(float x)"),
+            (ptr(fn_ptr, false), "int32 (**x)(float x)"),
+            (CType::FnPtr { params: vec![], ret: Box::new(ptr(named("Foo"), false)) }, "Foo* (*x)(void)"),
+        ];
+        for (ty, decl) in expect {
+            assert_eq!(ty.declare("x"), decl);
+        }
+        assert_eq!(ptr(CType::Char, true).declare(""), "const ANSICHAR*");
+    }
+
+    #[test]
+    fn items() {
+        let api = parse(r#"
+            /// A point.
+            #[repr(C)]
+            pub struct Point { x: f32, y: f32 }
+            #[repr(u8)]
+            pub enum Mode { A, B = 4, C = -1 }
+            pub enum NotExported { A }
+            pub struct Handle;
+            mod inner {
+                #[no_mangle]
+                pub extern "C" fn point_len(p: &Point) -> f32 { 0.0 }
+            }
+            #[unsafe(no_mangle)]
+            pub extern "C" fn handle_new(mode: Mode) -> *mut Handle { todo!() }
+            #[no_mangle]
+            pub fn not_c() {}
+            pub extern "C" fn not_exported() {}
+        "#).unwrap();
+        assert_eq!(api.functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["point_len", "handle_new"]);
+        assert_eq!(api.structs[0].docs, ["A point."]);
+        assert_eq!(api.enums.len(), 1);
+        assert_eq!(api.enums[0].repr, CType::UInt8);
+        assert_eq!(api.enums[0].variants, [("A".to_string(), None), ("B".to_string(), Some(4)), ("C".to_string(), Some(-1))]);
+        assert_eq!(api.opaque_types(), ["Handle"]);
+        assert_eq!(api.functions[0].declare(), "float point_len(const Point* p)");
+    }
+
+    #[test]
+    fn unknown_types_by_value() {
+        for src in [
+            "#[no_mangle] pub extern \"C\" fn f(s: String) {}",
+            "#[no_mangle] pub extern \"C\" fn f() -> Handle { todo!() }",
+            "#[no_mangle] pub extern \"C\" fn f(cb: extern \"C\" fn(Handle)) {}",
+            "#[repr(C)] pub struct S { h: [Handle; 2] }",
+        ] {
+            assert!(matches!(parse(src), Err(Error::UnsupportedType(_))), "{src}");
+        }
+        assert!(parse("#[no_mangle] pub extern \"C\" fn f(h: *mut Handle, cb: extern \"C\" fn(*const Handle)) {}").is_ok());
+    }
+
+    #[test]
+    fn header_declares_imported_functions() {
+        let api = parse("#[no_mangle] pub extern \"C\" fn foo_init() {}").unwrap();
+        let header = api.header("Foo");
+        assert!(header.contains("extern \"C\" {\nvoid foo_init(void);\n}"), "{header}");
+        assert!(!header.contains("FOO_API"), "{header}");
+    }
+
+    #[test]
+    fn enum_reprs() {
+        let api = parse("#[repr(C, u8)] enum A { X } #[repr(u16, C)] enum B { X } #[repr(C)] enum C { X } #[repr(i64)] enum D { X } enum E { X }").unwrap();
+        let reprs = api.enums.iter().map(|e| (e.name.as_str(), e.repr.clone())).collect::<Vec<_>>();
+        assert_eq!(reprs, [("A", CType::UInt8), ("B", CType::UInt16), ("C", CType::Int32), ("D", CType::Int64)]);
+        assert!(api.header("Foo").contains("enum class A : uint8 {"));
+    }
+
+    #[test]
+    fn str_is_rejected() {
+        assert_eq!(ty("&str"), None);
+        assert_eq!(ty("*const str"), None);
+        let result = parse("#[no_mangle] pub extern \"C\" fn f(name: &str) {}");
+        assert!(matches!(&result, Err(Error::UnsupportedType(why)) if why == "f: name: & str"), "{result:?}");
+    }
+
+    #[test]
+    fn structs_follow_their_fields() {
+        let api = parse("#[repr(C)] struct A { b: B } #[repr(C)] struct B { c: [C; 2] } #[repr(C)] struct C { p: *const A }").unwrap();
+        let order = api.structs_in_declaration_order().iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(order, ["C", "B", "A"]);
+    }
+}
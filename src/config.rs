@@ -0,0 +1,181 @@
+//! Ini files in the plugin's `Config` directory.
+
+use super::platform::UnrealPlatform;
+use std::path::PathBuf;
+
+/// A line of an ini section.
+#[derive(Debug, Clone, Copy)]
+pub enum IniEntry<'a> {
+    /// `Key=Value`
+    Set(&'a str, &'a str),
+    /// `+Key=Value`, appends to an array unless already present.
+    Add(&'a str, &'a str),
+    /// `-Key=Value`, removes from an array.
+    Remove(&'a str, &'a str),
+    /// `!Key=ClearArray`, empties an array.
+    Clear(&'a str),
+    /// Written as is, e.g. the paths of `FilterPlugin.ini`.
+    Line(&'a str),
+}
+impl IniEntry<'_> {
+    fn to_line(self) -> String {
+        match self {
+            IniEntry::Set(key, value) => format!("{}={}", key, value),
+            IniEntry::Add(key, value) => format!("+{}={}", key, value),
+            IniEntry::Remove(key, value) => format!("-{}={}", key, value),
+            IniEntry::Clear(key) => format!("!{}=ClearArray", key),
+            IniEntry::Line(line) => line.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IniSection<'a> {
+    /// e.g. `/Script/Engine.RendererSettings`
+    pub name: &'a str,
+    pub entries: &'a [IniEntry<'a>],
+}
+
+/// Which file in `Config` a section is written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFile<'a> {
+    /// `Default<Plugin>.ini`
+    Plugin,
+    /// `Default<Category>.ini`, e.g. `Engine` or `Game`.
+    Default(&'a str),
+    /// `<Platform>/<Platform><Category>.ini`, e.g. `Android/AndroidEngine.ini`.
+    Platform(UnrealPlatform, &'a str),
+    /// `FilterPlugin.ini`, extra files packaged with the plugin.
+    FilterPlugin,
+}
+impl ConfigFile<'_> {
+    /// Path relative to the plugin's `Config` directory.
+    fn path(self, plugin_name: &str) -> PathBuf {
+        match self {
+            ConfigFile::Plugin => PathBuf::from(format!("Default{}.ini", plugin_name)),
+            ConfigFile::Default(category) => PathBuf::from(format!("Default{}.ini", category)),
+            ConfigFile::Platform(platform, category) => {
+                let name = platform.config_name();
+                PathBuf::from(name).join(format!("{}{}.ini", name, category))
+            }
+            ConfigFile::FilterPlugin => PathBuf::from("FilterPlugin.ini"),
+        }
+    }
+}
+
+/// Section names and their lines, in order.
+type Sections = Vec<(String, Vec<String>)>;
+
+/// The contents of the `Config` directory; sections added to the same file
+/// twice are merged.
+#[derive(Default)]
+pub(crate) struct ConfigFiles {
+    files: Vec<(PathBuf, Sections)>,
+}
+impl ConfigFiles {
+    pub(crate) fn add(&mut self, plugin_name: &str, file: ConfigFile, section: &str, lines: impl IntoIterator<Item = String>) {
+        let path = file.path(plugin_name);
+        let sections = match self.files.iter().position(|(p, _)| *p == path) {
+            Some(i) => &mut self.files[i].1,
+            None => {
+                self.files.push((path, vec![]));
+                &mut self.files.last_mut().unwrap().1
+            }
+        };
+        let existing = match sections.iter().position(|(name, _)| name == section) {
+            Some(i) => &mut sections[i].1,
+            None => {
+                sections.push((section.to_string(), vec![]));
+                &mut sections.last_mut().unwrap().1
+            }
+        };
+        for line in lines {
+            // a later `Key=Value` overrides rather than repeats the key
+            let replaced = set_key(&line).and_then(|key| existing.iter().position(|l| set_key(l) == Some(key)));
+            match replaced {
+                Some(i) => existing[i] = line,
+                None => existing.push(line),
+            }
+        }
+    }
+
+    pub(crate) fn add_sections(&mut self, plugin_name: &str, file: ConfigFile, sections: &[IniSection]) {
+        for section in sections {
+            self.add(plugin_name, file, section.name, section.entries.iter().map(|e| e.to_line()));
+        }
+    }
+
+    /// Each file's path relative to `Config` and its contents.
+    pub(crate) fn render(&self) -> Vec<(&PathBuf, String)> {
+        self.files
+            .iter()
+            .map(|(path, sections)| {
+                let mut out = vec![];
+                for (name, lines) in sections {
+                    if !out.is_empty() {
+                        out.push(String::new());
+                    }
+                    out.push(format!("[{}]", name));
+                    out.extend(lines.iter().cloned());
+                }
+                out.push(String::new());
+                (path, out.join("\n"))
+            })
+            .collect()
+    }
+}
+
+/// The key of a plain `Key=Value` line.
+fn set_key(line: &str) -> Option<&str> {
+    match line.split_once('=') {
+        Some((key, _)) if !key.starts_with(['+', '-', '!', '.', ';']) => Some(key),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_are_merged() {
+        let mut files = ConfigFiles::default();
+        files.add_sections("Foo", ConfigFile::Plugin, &[IniSection {
+            name: "/Script/Foo.FooSettings",
+            entries: &[IniEntry::Set("Scale", "1.0"), IniEntry::Add("Paths", "a"), IniEntry::Line("; kept")],
+        }]);
+        files.add_sections("Foo", ConfigFile::Default("Engine"), &[IniSection { name: "Core.Log", entries: &[IniEntry::Set("LogFoo", "Log")] }]);
+        files.add_sections("Foo", ConfigFile::Plugin, &[
+            IniSection { name: "Core.Log", entries: &[IniEntry::Set("LogFoo", "Verbose")] },
+            IniSection {
+                name: "/Script/Foo.FooSettings",
+                entries: &[IniEntry::Set("Scale", "2.0"), IniEntry::Add("Paths", "b"), IniEntry::Clear("Old"), IniEntry::Remove("Paths", "c")],
+            },
+        ]);
+
+        let rendered = files.render();
+        let paths = rendered.iter().map(|(p, _)| p.to_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(paths, ["DefaultFoo.ini", "DefaultEngine.ini"]);
+        assert_eq!(rendered[0].1, [
+            "[/Script/Foo.FooSettings]",
+            "Scale=2.0",
+            "+Paths=a",
+            "; kept",
+            "+Paths=b",
+            "!Old=ClearArray",
+            "-Paths=c",
+            "",
+            "[Core.Log]",
+            "LogFoo=Verbose",
+            "",
+        ].join("\n"));
+        assert_eq!(rendered[1].1, "[Core.Log]\nLogFoo=Log\n");
+    }
+
+    #[test]
+    fn platform_files() {
+        let path = ConfigFile::Platform(UnrealPlatform::Android, "Engine").path("Foo");
+        assert_eq!(path, PathBuf::from("Android").join("AndroidEngine.ini"));
+        assert_eq!(ConfigFile::FilterPlugin.path("Foo"), PathBuf::from("FilterPlugin.ini"));
+    }
+}
@@ -0,0 +1,327 @@
+//! An engine subsystem turning Rust callbacks into dynamic multicast
+//! delegates broadcast on the game thread.
+
+use super::{
+    capi::{CApi, CFunction, CType},
+    wrappers::{blueprint_type, is_len, pascal_case},
+    Error, Result, RustEvent,
+};
+
+/// An event with its registration function and callback signature.
+struct Event<'a> {
+    name: &'a str,
+    register: &'a CFunction,
+    params: &'a [(String, CType)],
+}
+
+pub(crate) struct Events<'a> {
+    events: Vec<Event<'a>>,
+    module_name: &'a str,
+}
+
+/// A callback parameter as copied on the calling thread.
+struct Arg {
+    ty: String,
+    var: String,
+    /// Declares and initializes `var` from the C arguments.
+    init: String,
+    movable: bool,
+}
+
+impl<'a> Events<'a> {
+    pub(crate) fn new(api: &'a CApi, events: &'a [RustEvent<'a>], module_name: &'a str) -> Result<Self> {
+        let events = events
+            .iter()
+            .map(|event| {
+                let register = api
+                    .functions
+                    .iter()
+                    .find(|f| f.name == event.register)
+                    .ok_or_else(|| Error::UnknownFunction(event.register.to_string()))?;
+                let invalid = |why: &str| Error::UnsupportedType(format!("{}: {}", register.name, why));
+                let mut callback = None;
+                for (_, ty) in &register.params {
+                    match ty {
+                        CType::FnPtr { params, ret } if callback.is_none() && **ret == CType::Void => callback = Some(params),
+                        ty if is_user_data(ty) => {}
+                        _ => return Err(invalid("expected a callback and an optional user data pointer")),
+                    }
+                }
+                let params = callback.ok_or_else(|| invalid("no callback parameter"))?;
+                Ok(Event { name: event.name, register, params })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { events, module_name })
+    }
+
+    fn class(&self) -> String {
+        format!("U{}Events", self.module_name)
+    }
+
+    fn delegate(&self, event: &Event) -> String {
+        format!("F{}{}", self.module_name, event.name)
+    }
+
+    /// The delegate parameters of `event`, skipping its user data pointer.
+    fn args(&self, event: &Event) -> Result<Vec<Arg>> {
+        let params = event.params;
+        let c_name = |i: usize| c_param_name(&params[i].0, i);
+        let mut out = vec![];
+        let mut i = 0;
+        while i < params.len() {
+            let (name, ty) = &params[i];
+            let var = if name.is_empty() { format!("Arg{}", i) } else { pascal_case(name) };
+            let c = c_name(i);
+            let (arg, used) = match ty {
+                ty if is_user_data(ty) => {
+                    i += 1;
+                    continue;
+                }
+                CType::Ptr { ty, is_const: true } if **ty == CType::Char && is_len(params, i) => {
+                    let len = c_name(i + 1);
+                    let init = format!("const FUTF8ToTCHAR {var}Utf8({c}, {len}); FString {var}({var}Utf8.Length(), {var}Utf8.Get());");
+                    (Arg { ty: "FString".to_string(), var, init, movable: true }, 2)
+                }
+                CType::Ptr { ty, is_const: true } if **ty == CType::Char => {
+                    let init = format!("FString {var} = {c} ? FString(UTF8_TO_TCHAR({c})) : FString();");
+                    (Arg { ty: "FString".to_string(), var, init, movable: true }, 1)
+                }
+                CType::Ptr { ty, is_const: true } if **ty == CType::UInt8 && is_len(params, i) => {
+                    let init = format!("TArray<uint8> {var}({c}, {});", c_name(i + 1));
+                    (Arg { ty: "TArray<uint8>".to_string(), var, init, movable: true }, 2)
+                }
+                ty => {
+                    let bp = blueprint_type(ty).ok_or_else(|| {
+                        Error::UnsupportedType(format!("{}: {}: not a Blueprint type", event.register.name, name))
+                    })?;
+                    let value = if bp == ty.declare("") { c } else { format!("static_cast<{}>({})", bp, c) };
+                    (Arg { ty: bp.to_string(), init: format!("{} {} = {};", bp, var, value), var, movable: false }, 1)
+                }
+            };
+            out.push(arg);
+            i += used;
+        }
+        Ok(out)
+    }
+
+    /// `Public/<Module>Events.h`
+    pub(crate) fn header(&self) -> Result<String> {
+        let api_macro = format!("{}_API", self.module_name.to_uppercase());
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+            "#include \"Subsystems/EngineSubsystem.h\"".to_string(),
+            format!("#include \"{}Events.generated.h\"", self.module_name),
+            String::new(),
+        ];
+        for event in &self.events {
+            let args = self.args(event)?;
+            let delegate = self.delegate(event);
+            out.push(match args.len() {
+                0 => format!("DECLARE_DYNAMIC_MULTICAST_DELEGATE({});", delegate),
+                n => {
+                    let params = args
+                        .iter()
+                        .map(|a| if a.movable { format!(", const {}&, {}", a.ty, a.var) } else { format!(", {}, {}", a.ty, a.var) })
+                        .collect::<String>();
+                    format!("DECLARE_DYNAMIC_MULTICAST_DELEGATE_{}({}{});", param_count(n)?, delegate, params)
+                }
+            });
+        }
+        out.push(String::new());
+        out.push(format!("/** Events of the {} Rust library, broadcast on the game thread. */", self.module_name));
+        out.push("UCLASS()".to_string());
+        out.push(format!("class {} {} : public UEngineSubsystem", api_macro, self.class()));
+        out.push("{".to_string());
+        out.push("    GENERATED_BODY()".to_string());
+        out.push(String::new());
+        out.push("public:".to_string());
+        out.push("    virtual void Initialize(FSubsystemCollectionBase& Collection) override;".to_string());
+        out.push("    virtual void Deinitialize() override;".to_string());
+        for event in &self.events {
+            out.push(String::new());
+            out.push(format!("    UPROPERTY(BlueprintAssignable, Category = \"{}\")", self.module_name));
+            out.push(format!("    {} {};", self.delegate(event), event.name));
+        }
+        out.push("};".to_string());
+        out.push(String::new());
+        Ok(out.join("\n"))
+    }
+
+    /// `Private/<Module>Events.cpp`
+    pub(crate) fn source(&self) -> Result<String> {
+        let class = self.class();
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}Events.h\"", self.module_name),
+            "#include \"Async/Async.h\"".to_string(),
+            "#include \"Engine/Engine.h\"".to_string(),
+            format!("#include \"{}RustApi.h\"", self.module_name),
+            String::new(),
+            "namespace {".to_string(),
+        ];
+        for (i, event) in self.events.iter().enumerate() {
+            let args = self.args(event)?;
+            let c_params = event.params
+                .iter()
+                .enumerate()
+                .map(|(i, (name, ty))| ty.declare(&c_param_name(name, i)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if i > 0 {
+                out.push(String::new());
+            }
+            out.push("// may be called from any thread".to_string());
+            out.push(format!("void {}Callback({}) {{", event.name, c_params));
+            for arg in &args {
+                out.push(format!("    {}", arg.init));
+            }
+            let captures = args
+                .iter()
+                .map(|a| if a.movable { format!("{0} = MoveTemp({0})", a.var) } else { a.var.clone() })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let vars = args.iter().map(|a| a.var.as_str()).collect::<Vec<_>>().join(", ");
+            out.push(format!("    AsyncTask(ENamedThreads::GameThread, [{}]() {{", captures));
+            // lower case, so no captured argument can shadow it
+            out.push(format!("        if ({}* subsystem = GEngine ? GEngine->GetEngineSubsystem<{}>() : nullptr) {{", class, class));
+            out.push(format!("            subsystem->{}.Broadcast({});", event.name, vars));
+            out.push("        }".to_string());
+            out.push("    });".to_string());
+            out.push("}".to_string());
+        }
+        out.push("}".to_string());
+
+        let register = |event: &Event, on: bool| {
+            let args = event.register.params
+                .iter()
+                .map(|(_, ty)| match (ty, on) {
+                    (CType::FnPtr { .. }, true) => format!("&{}Callback", event.name),
+                    (_, true) => "this".to_string(),
+                    (_, false) => "nullptr".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("    {}({});", event.register.name, args)
+        };
+        out.push(String::new());
+        out.push(format!("void {}::Initialize(FSubsystemCollectionBase& Collection) {{", class));
+        out.push("    Super::Initialize(Collection);".to_string());
+        for event in &self.events {
+            out.push(register(event, true));
+        }
+        out.push("}".to_string());
+        out.push(String::new());
+        out.push(format!("void {}::Deinitialize() {{", class));
+        for event in &self.events {
+            out.push(register(event, false));
+        }
+        out.push("    Super::Deinitialize();".to_string());
+        out.push("}".to_string());
+        out.push(String::new());
+        Ok(out.join("\n"))
+    }
+}
+
+fn is_user_data(ty: &CType) -> bool {
+    matches!(ty, CType::Ptr { ty, is_const: false } if **ty == CType::Void)
+}
+
+fn c_param_name(name: &str, i: usize) -> String {
+    if name.is_empty() { format!("arg{}", i) } else { name.to_string() }
+}
+
+/// The suffix of the `DECLARE_DYNAMIC_MULTICAST_DELEGATE_*` macro.
+fn param_count(n: usize) -> Result<&'static str> {
+    const NAMES: [&str; 9] = [
+        "OneParam", "TwoParams", "ThreeParams", "FourParams", "FiveParams",
+        "SixParams", "SevenParams", "EightParams", "NineParams",
+    ];
+    NAMES.get(n - 1).copied().ok_or_else(|| Error::UnsupportedType(format!("delegates take at most 9 parameters, not {}", n)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(name: &str, callback: &[(&str, CType)], user_data: bool) -> CFunction {
+        let params = callback.iter().map(|(n, ty)| (n.to_string(), ty.clone())).collect();
+        let mut register = vec![("callback".to_string(), CType::FnPtr { params, ret: Box::new(CType::Void) })];
+        if user_data {
+            register.push(("user_data".to_string(), CType::Ptr { ty: Box::new(CType::Void), is_const: false }));
+        }
+        CFunction { name: name.to_string(), params: register, ret: CType::Void, docs: vec![] }
+    }
+
+    #[test]
+    fn events() {
+        let chars = CType::Ptr { ty: Box::new(CType::Char), is_const: true };
+        let user_data = CType::Ptr { ty: Box::new(CType::Void), is_const: false };
+        let api = CApi {
+            functions: vec![
+                register("on_ready", &[], false),
+                register("on_message", &[("user_data", user_data), ("events", chars), ("count", CType::UInt16)], true),
+            ],
+            ..CApi::default()
+        };
+        let events = [RustEvent { name: "Ready", register: "on_ready" }, RustEvent { name: "Message", register: "on_message" }];
+        let events = Events::new(&api, &events, "Foo").unwrap();
+        let header = events.header().unwrap();
+        let source = events.source().unwrap();
+        assert!(header.contains("DECLARE_DYNAMIC_MULTICAST_DELEGATE(FFooReady);"), "{header}");
+        assert!(header.contains("DECLARE_DYNAMIC_MULTICAST_DELEGATE_TwoParams(FFooMessage, const FString&, Events, int32, Count);"), "{header}");
+        assert!(header.contains("    UPROPERTY(BlueprintAssignable, Category = \"Foo\")\n    FFooMessage Message;"), "{header}");
+
+        // copied on the calling thread, broadcast on the game thread
+        assert!(source.contains("void MessageCallback(void* user_data, const ANSICHAR* events, uint16 count) {"), "{source}");
+        assert!(source.contains("    FString Events = events ? FString(UTF8_TO_TCHAR(events)) : FString();\n    int32 Count = static_cast<int32>(count);"), "{source}");
+        assert!(source.contains(concat!(
+            "    AsyncTask(ENamedThreads::GameThread, [Events = MoveTemp(Events), Count]() {\n",
+            "        if (UFooEvents* subsystem = GEngine ? GEngine->GetEngineSubsystem<UFooEvents>() : nullptr) {\n",
+            "            subsystem->Message.Broadcast(Events, Count);\n",
+        )), "{source}");
+
+        // user data is the subsystem, and every callback is cleared before it goes away
+        assert!(source.contains("    Super::Initialize(Collection);\n    on_ready(&ReadyCallback);\n    on_message(&MessageCallback, this);\n}"), "{source}");
+        assert!(source.contains("    on_ready(nullptr);\n    on_message(nullptr, nullptr);\n    Super::Deinitialize();\n}"), "{source}");
+    }
+
+    #[test]
+    fn byte_and_string_lengths() {
+        let chars = CType::Ptr { ty: Box::new(CType::Char), is_const: true };
+        let bytes = CType::Ptr { ty: Box::new(CType::UInt8), is_const: true };
+        let api = CApi {
+            functions: vec![register("on_data", &[("name", chars), ("name_len", CType::Size), ("data", bytes), ("len", CType::Size)], false)],
+            ..CApi::default()
+        };
+        let events = [RustEvent { name: "Data", register: "on_data" }];
+        let events = Events::new(&api, &events, "Foo").unwrap();
+        let header = events.header().unwrap();
+        let source = events.source().unwrap();
+        assert!(header.contains("DECLARE_DYNAMIC_MULTICAST_DELEGATE_TwoParams(FFooData, const FString&, Name, const TArray<uint8>&, Data);"), "{header}");
+        assert!(source.contains("    const FUTF8ToTCHAR NameUtf8(name, name_len); FString Name(NameUtf8.Length(), NameUtf8.Get());"), "{source}");
+        assert!(source.contains("    TArray<uint8> Data(data, len);"), "{source}");
+        assert!(source.contains("[Name = MoveTemp(Name), Data = MoveTemp(Data)]"), "{source}");
+    }
+
+    #[test]
+    fn unsupported_events() {
+        let floats = CType::Ptr { ty: Box::new(CType::Float), is_const: true };
+        let mut no_callback = register("on_nothing", &[], false);
+        no_callback.params.clear();
+        let api = CApi {
+            functions: vec![register("on_samples", &[("samples", floats)], false), no_callback],
+            ..CApi::default()
+        };
+        let events = [RustEvent { name: "Samples", register: "on_samples" }];
+        assert!(matches!(Events::new(&api, &events, "Foo").unwrap().header(), Err(Error::UnsupportedType(_))));
+        let events = [RustEvent { name: "Nothing", register: "on_nothing" }];
+        assert!(matches!(Events::new(&api, &events, "Foo"), Err(Error::UnsupportedType(_))));
+        let events = [RustEvent { name: "Missing", register: "on_missing" }];
+        assert!(matches!(Events::new(&api, &events, "Foo"), Err(Error::UnknownFunction(_))));
+    }
+}
@@ -0,0 +1,143 @@
+//! iOS Unreal Plugin Language (UPL) configuration for a module.
+
+use super::android::xml_escape;
+
+/// A value in the app's `Info.plist` or entitlements.
+#[derive(Debug, Clone, Copy)]
+pub enum PlistValue<'a> {
+    Bool(bool),
+    Int(i64),
+    String(&'a str),
+    Array(&'a [&'a str]),
+}
+impl PlistValue<'_> {
+    fn to_xml(self) -> String {
+        match self {
+            PlistValue::Bool(b) => format!("<{}/>", b),
+            PlistValue::Int(i) => format!("<integer>{}</integer>", i),
+            PlistValue::String(s) => format!("<string>{}</string>", xml_escape(s)),
+            PlistValue::Array(items) => {
+                let items = items
+                    .iter()
+                    .map(|i| format!("<string>{}</string>", xml_escape(i)))
+                    .collect::<String>();
+                format!("<array>{}</array>", items)
+            }
+        }
+    }
+}
+
+/// What a module adds to the app's `Info.plist` through its `IOS_UPL.xml`.
+#[derive(Default)]
+pub struct IosConfig<'a> {
+    /// `(key, text)` privacy strings, e.g.
+    /// `("NSCameraUsageDescription", "Scans QR codes")`.
+    pub usage_descriptions: &'a [(&'a str, &'a str)],
+    /// `UIBackgroundModes` entries, e.g. `audio` or `remote-notification`.
+    pub background_modes: &'a [&'a str],
+    /// Schemes registered under `CFBundleURLTypes`, e.g. `myapp`.
+    pub url_schemes: &'a [&'a str],
+    /// Other `Info.plist` keys.
+    pub plist_entries: &'a [(&'a str, PlistValue<'a>)],
+    /// Written to `IOS/<Module>.entitlements`, e.g.
+    /// `("com.apple.developer.associated-domains", PlistValue::Array(&["applinks:example.com"]))`.
+    ///
+    /// UBT only signs the app with the project's entitlements and has no UPL
+    /// hook to extend them, so the file takes effect once the project's
+    /// `PremadeIOSEntitlements` (`[/Script/MacTargetPlatform.XcodeProjectSettings]`
+    /// in `DefaultEngine.ini`) points at it, or its keys are copied into the
+    /// project's own entitlements.
+    pub entitlements: &'a [(&'a str, PlistValue<'a>)],
+}
+impl IosConfig<'_> {
+    /// `<key>`/value lines added to the `Info.plist` root `<dict>`.
+    pub(crate) fn plist_lines(&self) -> Vec<String> {
+        let mut out = vec![];
+        let mut entry = |key: &str, value: PlistValue| {
+            out.push(format!("<key>{}</key>", xml_escape(key)));
+            out.push(value.to_xml());
+        };
+        for (key, text) in self.usage_descriptions {
+            entry(key, PlistValue::String(text));
+        }
+        if !self.background_modes.is_empty() {
+            entry("UIBackgroundModes", PlistValue::Array(self.background_modes));
+        }
+        for (key, value) in self.plist_entries {
+            entry(key, *value);
+        }
+        if !self.url_schemes.is_empty() {
+            out.push("<key>CFBundleURLTypes</key>".to_string());
+            out.push("<array>".to_string());
+            out.push("    <dict>".to_string());
+            out.push("        <key>CFBundleURLSchemes</key>".to_string());
+            out.push(format!("        {}", PlistValue::Array(self.url_schemes).to_xml()));
+            out.push("    </dict>".to_string());
+            out.push("</array>".to_string());
+        }
+        out
+    }
+
+    pub(crate) fn entitlement_lines(&self) -> Vec<String> {
+        self.entitlements
+            .iter()
+            .flat_map(|(key, value)| [format!("<key>{}</key>", xml_escape(key)), value.to_xml()])
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plist() {
+        let ios = IosConfig {
+            usage_descriptions: &[("NSCameraUsageDescription", "Scans <QR> codes & more")],
+            background_modes: &["audio", "remote-notification"],
+            url_schemes: &["myapp"],
+            plist_entries: &[("UIFileSharingEnabled", PlistValue::Bool(true)), ("Retries", PlistValue::Int(-3))],
+            ..Default::default()
+        };
+        assert_eq!(ios.plist_lines(), [
+            "<key>NSCameraUsageDescription</key>",
+            "<string>Scans &lt;QR&gt; codes &amp; more</string>",
+            "<key>UIBackgroundModes</key>",
+            "<array><string>audio</string><string>remote-notification</string></array>",
+            "<key>UIFileSharingEnabled</key>",
+            "<true/>",
+            "<key>Retries</key>",
+            "<integer>-3</integer>",
+            "<key>CFBundleURLTypes</key>",
+            "<array>",
+            "    <dict>",
+            "        <key>CFBundleURLSchemes</key>",
+            "        <array><string>myapp</string></array>",
+            "    </dict>",
+            "</array>",
+        ]);
+        assert!(IosConfig::default().plist_lines().is_empty());
+    }
+
+    #[test]
+    fn entitlements() {
+        let ios = IosConfig {
+            entitlements: &[
+                ("com.apple.developer.associated-domains", PlistValue::Array(&["applinks:a.com?x=1&y=2"])),
+                ("aps-environment", PlistValue::String("production")),
+                ("com.apple.developer.game-center", PlistValue::Bool(false)),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(ios.entitlement_lines(), [
+            "<key>com.apple.developer.associated-domains</key>",
+            "<array><string>applinks:a.com?x=1&amp;y=2</string></array>",
+            "<key>aps-environment</key>",
+            "<string>production</string>",
+            "<key>com.apple.developer.game-center</key>",
+            "<false/>",
+        ]);
+        // entitlements aren't Info.plist keys
+        assert!(ios.plist_lines().is_empty());
+    }
+}
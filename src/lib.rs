@@ -1,18 +1,53 @@
 #![allow(unused_variables)]
 
 mod ueplugingen; 
+mod android;
+mod ios;
+pub mod platform;
+pub mod macho;
+pub mod capi;
+mod wrappers;
+mod reflect;
+mod events;
+mod settings;
+mod config;
+mod logging;
+mod unreal_log;
+mod templates;
+mod prebuild;
+pub mod profiles;
 
 pub use ueplugingen::*;
+pub use android::{AndroidConfig, AndroidFeature, AndroidPermission};
+pub use ios::{IosConfig, PlistValue};
+pub use logging::{ConsoleVariable, LogVerbosity, ModuleLog};
+pub use unreal_log::{UnrealLogCallback, UnrealLogger};
+pub use templates::TemplateOverrides;
+pub use config::{ConfigFile, IniEntry, IniSection};
+pub use settings::{DeveloperSettings, Setting, SettingValue};
+pub use platform::{UnrealArch, UnrealPlatform, UnrealTarget};
+pub use prebuild::PreBuildSteps;
+pub use profiles::CargoProfiles;
 
 #[derive(Debug)]
 pub enum Error {
     IoError(std::io::Error),
     VarError(std::env::VarError),
     AskamaError(askama::Error),
+    UnknownTarget(String),
+    InvalidMachO(String),
+    StageConflict(std::path::PathBuf),
+    /// A target staged without a cargo profile while library variants are on.
+    MissingProfile(String),
+    InvalidDefine(String),
+    SynError(syn::Error),
+    UnsupportedType(String),
+    UnknownFunction(String),
+    InvalidTemplate(String),
 }
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self)
+        write!(f, "{:?}", self)
     }
 }
 impl std::error::Error for Error {}
@@ -21,3 +56,4 @@ pub type Result<T> = std::result::Result<T, Error>;
 impl From<std::io::Error> for Error { fn from(value: std::io::Error) -> Self { Self::IoError(value) } }
 impl From<std::env::VarError> for Error { fn from(value: std::env::VarError) -> Self { Self::VarError(value) } }
 impl From<askama::Error> for Error { fn from(value: askama::Error) -> Self { Self::AskamaError(value) } }
+impl From<syn::Error> for Error { fn from(value: syn::Error) -> Self { Self::SynError(value) } }
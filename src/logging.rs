@@ -0,0 +1,251 @@
+//! `<Module>Log.h/.cpp`: the module's log category, stats group and
+//! console variables.
+
+use super::{
+    settings::SettingValue,
+    ueplugingen::c_escape,
+    wrappers::pascal_case,
+};
+use serde::Serialize;
+
+/// `ELogVerbosity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogVerbosity {
+    NoLogging,
+    Fatal,
+    Error,
+    Warning,
+    Display,
+    Log,
+    Verbose,
+    VeryVerbose,
+    All,
+}
+impl std::fmt::Display for LogVerbosity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// A `TAutoConsoleVariable` of the type of its default value.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ConsoleVariable<'a> {
+    /// e.g. `myplugin.MaxRetries`, declared as `CVarMypluginMaxRetries`.
+    pub name: &'a str,
+    pub value: SettingValue<'a>,
+    pub help: &'a str,
+}
+impl ConsoleVariable<'_> {
+    fn var(&self) -> String {
+        format!("CVar{}", self.name.split('.').map(pascal_case).collect::<String>())
+    }
+}
+
+#[derive(Serialize)]
+pub struct ModuleLog<'a> {
+    /// e.g. `LogMyPlugin`
+    pub category: &'a str,
+    pub verbosity: LogVerbosity,
+    /// Verbosity above which logging is compiled out.
+    pub compile_time_verbosity: LogVerbosity,
+    /// Declares `STATGROUP_<Name>`, shown by `stat <Name>`.
+    pub stats_group: Option<&'a str>,
+    pub console_variables: &'a [ConsoleVariable<'a>],
+    /// Rust function installing a callback that writes `log` crate records
+    /// to the category, called from `StartupModule` and with null from
+    /// `ShutdownModule`. Export it with
+    /// [`unreal_log_bridge!`](crate::unreal_log_bridge).
+    pub rust_log: Option<&'a str>,
+}
+impl<'a> ModuleLog<'a> {
+    pub fn new(category: &'a str) -> Self {
+        Self {
+            category,
+            verbosity: LogVerbosity::Log,
+            compile_time_verbosity: LogVerbosity::All,
+            stats_group: None,
+            console_variables: &[],
+            rust_log: None,
+        }
+    }
+
+    /// The function `rust_log` is installed with.
+    pub(crate) fn callback(module_name: &str) -> String {
+        format!("{}RustLog", module_name)
+    }
+
+    /// The function returning the category's verbosity as a
+    /// `log::LevelFilter`.
+    pub(crate) fn level(module_name: &str) -> String {
+        format!("{}RustLogLevel", module_name)
+    }
+
+    /// `Public/<Module>Log.h`
+    pub(crate) fn header(&self, module_name: &str) -> String {
+        let api_macro = format!("{}_API", module_name.to_uppercase());
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+        ];
+        if !self.console_variables.is_empty() {
+            out.push("#include \"HAL/IConsoleManager.h\"".to_string());
+        }
+        out.push("#include \"Logging/LogMacros.h\"".to_string());
+        if self.stats_group.is_some() {
+            out.push("#include \"Stats/Stats.h\"".to_string());
+        }
+        out.push(String::new());
+        out.push(format!(
+            "{} DECLARE_LOG_CATEGORY_EXTERN({}, {}, {});",
+            api_macro, self.category, self.verbosity, self.compile_time_verbosity
+        ));
+        if let Some(group) = self.stats_group {
+            out.push(String::new());
+            out.push(format!("DECLARE_STATS_GROUP(TEXT(\"{}\"), STATGROUP_{}, STATCAT_Advanced);", c_escape(group), group));
+        }
+        if !self.console_variables.is_empty() {
+            out.push(String::new());
+            for cvar in self.console_variables {
+                out.push(format!("extern {} TAutoConsoleVariable<{}> {};", api_macro, cvar.value.cpp_type(), cvar.var()));
+            }
+        }
+        if self.rust_log.is_some() {
+            out.push(String::new());
+            out.push(format!("/** Writes a Rust `log` record to {}, from any thread. */", self.category));
+            out.push(format!(
+                "void {}(uint8 Level, const ANSICHAR* Target, SIZE_T TargetLen, const ANSICHAR* Message, SIZE_T MessageLen);",
+                Self::callback(module_name)
+            ));
+            out.push(format!("/** The verbosity of {} as a Rust `log::LevelFilter`. */", self.category));
+            out.push(format!("uint8 {}();", Self::level(module_name)));
+        }
+        out.push(String::new());
+        out.join("\n")
+    }
+
+    /// `Private/<Module>Log.cpp`
+    pub(crate) fn source(&self, module_name: &str) -> String {
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}Log.h\"", module_name),
+            String::new(),
+            format!("DEFINE_LOG_CATEGORY({});", self.category),
+        ];
+        if !self.console_variables.is_empty() {
+            out.push(String::new());
+            for cvar in self.console_variables {
+                out.push(format!(
+                    "TAutoConsoleVariable<{}> {}(TEXT(\"{}\"), {}, TEXT(\"{}\"), ECVF_Default);",
+                    cvar.value.cpp_type(),
+                    cvar.var(),
+                    c_escape(cvar.name),
+                    cvar.value.cpp_literal(),
+                    c_escape(cvar.help)
+                ));
+            }
+        }
+        if self.rust_log.is_some() {
+            out.push(String::new());
+            out.push(format!(
+                "void {}(uint8 Level, const ANSICHAR* Target, SIZE_T TargetLen, const ANSICHAR* Message, SIZE_T MessageLen) {{",
+                Self::callback(module_name)
+            ));
+            out.push("    const FUTF8ToTCHAR TargetUtf8(Target, TargetLen);".to_string());
+            out.push("    const FUTF8ToTCHAR MessageUtf8(Message, MessageLen);".to_string());
+            out.push("    const FString TargetStr(TargetUtf8.Length(), TargetUtf8.Get());".to_string());
+            out.push("    const FString MessageStr(MessageUtf8.Length(), MessageUtf8.Get());".to_string());
+            out.push("    // log::Level: Error = 1, Warn, Info, Debug, Trace".to_string());
+            out.push("    switch (Level) {".to_string());
+            for (level, verbosity) in [(1, "Error"), (2, "Warning"), (3, "Log"), (4, "Verbose")] {
+                out.push(format!(
+                    "    case {}: UE_LOG({}, {}, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+                    level, self.category, verbosity
+                ));
+            }
+            out.push(format!(
+                "    default: UE_LOG({}, VeryVerbose, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+                self.category
+            ));
+            out.push("    }".to_string());
+            out.push("}".to_string());
+            out.push(String::new());
+            out.push(format!("uint8 {}() {{", Self::level(module_name)));
+            out.push("    // log::LevelFilter: Off = 0, Error, Warn, Info, Debug, Trace".to_string());
+            out.push(format!("    switch ({}.GetVerbosity()) {{", self.category));
+            out.push("    case ELogVerbosity::NoLogging: return 0;".to_string());
+            out.push("    case ELogVerbosity::Fatal:".to_string());
+            out.push("    case ELogVerbosity::Error: return 1;".to_string());
+            out.push("    case ELogVerbosity::Warning: return 2;".to_string());
+            out.push("    case ELogVerbosity::Display:".to_string());
+            out.push("    case ELogVerbosity::Log: return 3;".to_string());
+            out.push("    case ELogVerbosity::Verbose: return 4;".to_string());
+            out.push("    default: return 5;".to_string());
+            out.push("    }".to_string());
+            out.push("}".to_string());
+        }
+        out.push(String::new());
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: ModuleLog = ModuleLog {
+        category: "LogFoo",
+        verbosity: LogVerbosity::Warning,
+        compile_time_verbosity: LogVerbosity::Verbose,
+        stats_group: Some("Foo"),
+        console_variables: &[
+            ConsoleVariable { name: "foo.max_retries", value: SettingValue::Int(3), help: "Retries, \"0\" to disable" },
+            ConsoleVariable { name: "foo.Scale", value: SettingValue::Float(0.5), help: "" },
+        ],
+        rust_log: Some("foo_set_logger"),
+    };
+
+    #[test]
+    fn header() {
+        let header = LOG.header("Foo");
+        assert!(header.contains("FOO_API DECLARE_LOG_CATEGORY_EXTERN(LogFoo, Warning, Verbose);"), "{header}");
+        assert!(header.contains("DECLARE_STATS_GROUP(TEXT(\"Foo\"), STATGROUP_Foo, STATCAT_Advanced);"), "{header}");
+        assert!(header.contains("extern FOO_API TAutoConsoleVariable<int32> CVarFooMaxRetries;"), "{header}");
+        assert!(header.contains("extern FOO_API TAutoConsoleVariable<float> CVarFooScale;"), "{header}");
+        assert!(header.contains("void FooRustLog(uint8 Level, const ANSICHAR* Target, SIZE_T TargetLen, const ANSICHAR* Message, SIZE_T MessageLen);"), "{header}");
+        assert!(header.contains("uint8 FooRustLogLevel();"), "{header}");
+    }
+
+    #[test]
+    fn source() {
+        let source = LOG.source("Foo");
+        assert!(source.contains("DEFINE_LOG_CATEGORY(LogFoo);"), "{source}");
+        assert!(source.contains(
+            "TAutoConsoleVariable<int32> CVarFooMaxRetries(TEXT(\"foo.max_retries\"), 3, TEXT(\"Retries, \\\"0\\\" to disable\"), ECVF_Default);"
+        ), "{source}");
+        assert!(source.contains("TAutoConsoleVariable<float> CVarFooScale(TEXT(\"foo.Scale\"), 0.5f, TEXT(\"\"), ECVF_Default);"), "{source}");
+        for expected in [
+            "    case 1: UE_LOG(LogFoo, Error, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+            "    case 2: UE_LOG(LogFoo, Warning, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+            "    case 3: UE_LOG(LogFoo, Log, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+            "    case 4: UE_LOG(LogFoo, Verbose, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+            "    default: UE_LOG(LogFoo, VeryVerbose, TEXT(\"%s: %s\"), *TargetStr, *MessageStr); break;",
+            "    switch (LogFoo.GetVerbosity()) {",
+        ] {
+            assert!(source.contains(expected), "{expected} missing from {source}");
+        }
+    }
+
+    #[test]
+    fn without_extras() {
+        let log = ModuleLog::new("LogFoo");
+        let header = log.header("Foo");
+        assert!(header.contains("FOO_API DECLARE_LOG_CATEGORY_EXTERN(LogFoo, Log, All);"), "{header}");
+        assert!(!header.contains("Stats.h") && !header.contains("IConsoleManager.h") && !header.contains("FooRustLog"), "{header}");
+        let source = log.source("Foo");
+        assert!(!source.contains("TAutoConsoleVariable") && !source.contains("switch"), "{source}");
+    }
+}
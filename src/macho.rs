@@ -0,0 +1,132 @@
+//! Minimal `lipo -create`: merges thin Mach-O images into a universal binary.
+
+use super::{Error, Result};
+
+const MH_MAGIC: u32 = 0xfeedface;
+const MH_MAGIC_64: u32 = 0xfeedfacf;
+const FAT_MAGIC: u32 = 0xcafebabe;
+
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_TYPE_ARM64: u32 = 0x0100_000c;
+
+const FAT_HEADER_SIZE: usize = 8;
+const FAT_ARCH_SIZE: usize = 20;
+
+struct Thin<'a> {
+    cputype: u32,
+    cpusubtype: u32,
+    align: u32,
+    bytes: &'a [u8],
+}
+
+fn parse_thin(bytes: &[u8]) -> Result<Thin<'_>> {
+    let word = |offset: usize, big_endian: bool| -> Option<u32> {
+        let b: [u8; 4] = bytes.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) })
+    };
+    let invalid = || Error::InvalidMachO("not a thin Mach-O image".to_string());
+
+    let big_endian = match word(0, false).ok_or_else(invalid)? {
+        MH_MAGIC | MH_MAGIC_64 => false,
+        m if m.swap_bytes() == MH_MAGIC || m.swap_bytes() == MH_MAGIC_64 => true,
+        m if m.swap_bytes() == FAT_MAGIC => {
+            return Err(Error::InvalidMachO("image is already universal".to_string()))
+        }
+        _ => return Err(invalid()),
+    };
+    let cputype = word(4, big_endian).ok_or_else(invalid)?;
+    let cpusubtype = word(8, big_endian).ok_or_else(invalid)?;
+    // same alignment lipo picks: the page size of the architecture
+    let align = match cputype {
+        CPU_TYPE_ARM64 => 14,
+        CPU_TYPE_X86_64 => 12,
+        _ => 12,
+    };
+    Ok(Thin { cputype, cpusubtype, align, bytes })
+}
+
+/// Builds a universal (fat) Mach-O from thin images, one per architecture.
+pub fn create_universal(images: &[&[u8]]) -> Result<Vec<u8>> {
+    let mut thins = images.iter().map(|i| parse_thin(i)).collect::<Result<Vec<_>>>()?;
+    thins.sort_by_key(|t| t.cputype);
+    if thins.windows(2).any(|w| w[0].cputype == w[1].cputype) {
+        return Err(Error::InvalidMachO("duplicate architecture".to_string()));
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&FAT_MAGIC.to_be_bytes());
+    out.extend_from_slice(&(thins.len() as u32).to_be_bytes());
+
+    let mut offset = FAT_HEADER_SIZE + FAT_ARCH_SIZE * thins.len();
+    let mut offsets = vec![];
+    for thin in &thins {
+        let align = 1usize << thin.align;
+        offset = offset.div_ceil(align) * align;
+        offsets.push(offset);
+        // fat_arch has 32-bit offsets and sizes
+        let too_large = |_| Error::InvalidMachO("universal binary larger than 4 GiB".to_string());
+        let fat_offset = u32::try_from(offset).map_err(too_large)?;
+        let size = u32::try_from(thin.bytes.len()).map_err(too_large)?;
+        for word in [thin.cputype, thin.cpusubtype, fat_offset, size, thin.align] {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        offset += thin.bytes.len();
+    }
+    for (thin, offset) in thins.iter().zip(offsets) {
+        out.resize(offset, 0);
+        out.extend_from_slice(thin.bytes);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thin(cputype: u32, len: usize) -> Vec<u8> {
+        let mut out = vec![0xab; len];
+        out[..4].copy_from_slice(&MH_MAGIC_64.to_le_bytes());
+        out[4..8].copy_from_slice(&cputype.to_le_bytes());
+        out[8..12].copy_from_slice(&3u32.to_le_bytes());
+        out
+    }
+
+    fn word(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn universal_layout() {
+        let (arm64, x64) = (thin(CPU_TYPE_ARM64, 100), thin(CPU_TYPE_X86_64, 50));
+        let fat = create_universal(&[&arm64, &x64]).unwrap();
+
+        assert_eq!(fat[..4], [0xca, 0xfe, 0xba, 0xbe]);
+        assert_eq!(word(&fat, 4), 2);
+        // sorted by cputype: x86_64, then arm64
+        let arch = |i: usize| (0..5).map(|w| word(&fat, FAT_HEADER_SIZE + FAT_ARCH_SIZE * i + 4 * w)).collect::<Vec<_>>();
+        assert_eq!(arch(0), [CPU_TYPE_X86_64, 3, 1 << 12, 50, 12]);
+        assert_eq!(arch(1), [CPU_TYPE_ARM64, 3, 1 << 14, 100, 14]);
+        assert_eq!(fat[1 << 12..(1 << 12) + 50], x64[..]);
+        assert_eq!(fat[1 << 14..], arm64[..]);
+        assert!(fat[FAT_HEADER_SIZE + 2 * FAT_ARCH_SIZE..1 << 12].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn big_endian_images() {
+        let mut image = vec![0; 16];
+        image[..4].copy_from_slice(&MH_MAGIC.to_be_bytes());
+        image[4..8].copy_from_slice(&CPU_TYPE_X86_64.to_be_bytes());
+        let fat = create_universal(&[&image]).unwrap();
+        assert_eq!(word(&fat, FAT_HEADER_SIZE), CPU_TYPE_X86_64);
+    }
+
+    #[test]
+    fn invalid_images() {
+        let arm64 = thin(CPU_TYPE_ARM64, 16);
+        assert!(matches!(create_universal(&[&arm64, &arm64]), Err(Error::InvalidMachO(_))));
+        assert!(matches!(create_universal(&[b"MZ\x90\0 not a Mach-O"]), Err(Error::InvalidMachO(_))));
+        assert!(matches!(create_universal(&[&arm64[..2]]), Err(Error::InvalidMachO(_))));
+        let fat = create_universal(&[&arm64]).unwrap();
+        assert!(matches!(create_universal(&[&fat]), Err(Error::InvalidMachO(_))));
+    }
+}
@@ -0,0 +1,402 @@
+//! Mapping from cargo target triples to Unreal Engine platforms.
+//!
+//! Everything the generator needs to know about where a Rust library for a
+//! given triple ends up in the plugin (staging folder, `build.cs` branch,
+//! file names) is derived from [`UnrealTarget`], so the different parts of
+//! the output always agree with each other.
+
+use std::path::PathBuf;
+
+/// An `UnrealTargetPlatform` value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UnrealPlatform {
+    Win64,
+    Mac,
+    Linux,
+    LinuxArm64,
+    Android,
+    IOS,
+    TVOS,
+}
+impl UnrealPlatform {
+    pub const ALL: &'static [UnrealPlatform] = &[
+        Self::Win64,
+        Self::Mac,
+        Self::Linux,
+        Self::LinuxArm64,
+        Self::Android,
+        Self::IOS,
+        Self::TVOS,
+    ];
+
+    /// Name as spelled in `UnrealTargetPlatform.<Name>` and in .uplugin platform lists.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Win64 => "Win64",
+            Self::Mac => "Mac",
+            Self::Linux => "Linux",
+            Self::LinuxArm64 => "LinuxArm64",
+            Self::Android => "Android",
+            Self::IOS => "IOS",
+            Self::TVOS => "TVOS",
+        }
+    }
+
+    /// Name of the platform's `Config/<Name>/<Name><Category>.ini` files.
+    pub fn config_name(self) -> &'static str {
+        match self {
+            Self::Win64 => "Windows",
+            platform => platform.name(),
+        }
+    }
+
+    /// Preprocessor condition that holds when compiling for the platform.
+    pub fn cpp_condition(self) -> &'static str {
+        match self {
+            Self::Win64 => "PLATFORM_WINDOWS",
+            Self::Mac => "PLATFORM_MAC",
+            Self::Linux => "(PLATFORM_LINUX && !PLATFORM_CPU_ARM_FAMILY)",
+            Self::LinuxArm64 => "(PLATFORM_LINUX && PLATFORM_CPU_ARM_FAMILY)",
+            Self::Android => "PLATFORM_ANDROID",
+            Self::IOS => "(PLATFORM_IOS && !PLATFORM_TVOS)",
+            Self::TVOS => "PLATFORM_TVOS",
+        }
+    }
+
+    pub fn is_apple(self) -> bool {
+        matches!(self, Self::Mac | Self::IOS | Self::TVOS)
+    }
+
+    pub fn is_desktop(self) -> bool {
+        matches!(self, Self::Win64 | Self::Mac | Self::Linux | Self::LinuxArm64)
+    }
+}
+impl std::fmt::Display for UnrealPlatform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// CPU architecture of a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UnrealArch {
+    X64,
+    Arm64,
+    Armv7,
+    X86,
+}
+
+/// Toolchain flavour, only relevant for naming Windows import libraries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TargetEnv {
+    Msvc,
+    Gnu,
+    Other,
+}
+
+/// A cargo target triple resolved to its Unreal platform and architecture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UnrealTarget {
+    pub triple: &'static str,
+    pub platform: UnrealPlatform,
+    pub arch: UnrealArch,
+    pub env: TargetEnv,
+}
+
+macro_rules! targets {
+    ($($triple:literal => $platform:ident, $arch:ident, $env:ident;)*) => {
+        /// Every triple the generator knows how to place in a plugin.
+        pub const TARGETS: &[UnrealTarget] = &[$(
+            UnrealTarget {
+                triple: $triple,
+                platform: UnrealPlatform::$platform,
+                arch: UnrealArch::$arch,
+                env: TargetEnv::$env,
+            },
+        )*];
+    };
+}
+
+targets! {
+    "x86_64-pc-windows-msvc" => Win64, X64, Msvc;
+    "aarch64-pc-windows-msvc" => Win64, Arm64, Msvc;
+    "x86_64-pc-windows-gnu" => Win64, X64, Gnu;
+    "x86_64-pc-windows-gnullvm" => Win64, X64, Gnu;
+    "aarch64-pc-windows-gnullvm" => Win64, Arm64, Gnu;
+    "x86_64-apple-darwin" => Mac, X64, Other;
+    "aarch64-apple-darwin" => Mac, Arm64, Other;
+    "x86_64-unknown-linux-gnu" => Linux, X64, Other;
+    "aarch64-unknown-linux-gnu" => LinuxArm64, Arm64, Other;
+    "aarch64-linux-android" => Android, Arm64, Other;
+    "armv7-linux-androideabi" => Android, Armv7, Other;
+    "x86_64-linux-android" => Android, X64, Other;
+    "i686-linux-android" => Android, X86, Other;
+    "aarch64-apple-ios" => IOS, Arm64, Other;
+    "aarch64-apple-ios-sim" => IOS, Arm64, Other;
+    "x86_64-apple-ios" => IOS, X64, Other;
+    "aarch64-apple-tvos" => TVOS, Arm64, Other;
+    "aarch64-apple-tvos-sim" => TVOS, Arm64, Other;
+}
+
+impl UnrealTarget {
+    pub fn from_triple(triple: &str) -> Option<Self> {
+        TARGETS.iter().find(|t| t.triple == triple).copied()
+    }
+
+    /// Architecture name as Unreal spells it for this platform.
+    ///
+    /// Android uses ABI names (which is also what `$S(Architecture)` expands to
+    /// in UPL files), Linux uses the clang triple, everything else uses the
+    /// `UnrealArch` names.
+    pub fn arch_name(&self) -> &'static str {
+        match (self.platform, self.arch) {
+            (UnrealPlatform::Android, UnrealArch::Arm64) => "arm64-v8a",
+            (UnrealPlatform::Android, UnrealArch::Armv7) => "armeabi-v7a",
+            (UnrealPlatform::Android, UnrealArch::X64) => "x86_64",
+            (UnrealPlatform::Android, UnrealArch::X86) => "x86",
+            (UnrealPlatform::Linux, _) => "x86_64-unknown-linux-gnu",
+            (UnrealPlatform::LinuxArm64, _) => "aarch64-unknown-linux-gnueabi",
+            (_, UnrealArch::X64) => "x64",
+            (_, UnrealArch::Arm64) => "arm64",
+            (_, UnrealArch::Armv7) => "armv7",
+            (_, UnrealArch::X86) => "x86",
+        }
+    }
+
+    /// Folder, relative to the module directory, that libraries for this
+    /// target are staged into.
+    ///
+    /// Platforms built for several architectures get a per-architecture
+    /// subfolder, except Apple platforms, where the architectures are merged
+    /// into one universal library. Simulator libraries go into a `Simulator`
+    /// subfolder, a universal library can't hold them next to the device's.
+    pub fn stage_dir(&self) -> PathBuf {
+        self.stage_dir_components().iter().collect()
+    }
+
+    /// Whether libraries are staged per architecture.
+    pub fn has_arch_dir(&self) -> bool {
+        !self.platform.is_apple()
+    }
+
+    /// Whether this is an iOS or tvOS simulator target.
+    pub fn is_simulator(&self) -> bool {
+        match self.platform {
+            UnrealPlatform::IOS | UnrealPlatform::TVOS => self.triple.ends_with("-sim") || self.arch == UnrealArch::X64,
+            _ => false,
+        }
+    }
+
+    /// Same as [`stage_dir`](Self::stage_dir), as path components for `Path.Combine`.
+    pub fn stage_dir_components(&self) -> Vec<&'static str> {
+        if self.has_arch_dir() {
+            vec![self.platform.name(), self.arch_name()]
+        } else if self.is_simulator() {
+            vec![self.platform.name(), "Simulator"]
+        } else {
+            vec![self.platform.name()]
+        }
+    }
+
+    pub fn dylib_filename(&self, name: &str) -> String {
+        match self.platform {
+            UnrealPlatform::Win64 => format!("{name}.dll"),
+            p if p.is_apple() => format!("lib{name}.dylib"),
+            _ => format!("lib{name}.so"),
+        }
+    }
+
+    /// The import library that has to be linked against to use the dylib, if
+    /// the platform needs one.
+    pub fn import_lib_filename(&self, name: &str) -> Option<String> {
+        match (self.platform, self.env) {
+            (UnrealPlatform::Win64, TargetEnv::Gnu) => Some(format!("lib{name}.dll.a")),
+            (UnrealPlatform::Win64, _) => Some(format!("{name}.dll.lib")),
+            _ => None,
+        }
+    }
+
+    pub fn staticlib_filename(&self, name: &str) -> String {
+        match (self.platform, self.env) {
+            (UnrealPlatform::Win64, TargetEnv::Msvc) => format!("{name}.lib"),
+            _ => format!("lib{name}.a"),
+        }
+    }
+
+    /// The file that goes into `PublicAdditionalLibraries` for a dylib.
+    pub fn link_filename(&self, name: &str) -> String {
+        self.import_lib_filename(name)
+            .unwrap_or_else(|| self.dylib_filename(name))
+    }
+}
+impl std::fmt::Display for UnrealTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.triple)
+    }
+}
+
+/// Targets used for `build.cs` branches when the builder isn't given any.
+pub const DEFAULT_TARGETS: &[&str] = &[
+    "aarch64-linux-android",
+    "x86_64-pc-windows-msvc",
+    "aarch64-apple-darwin",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triples_are_unique() {
+        for (i, a) in TARGETS.iter().enumerate() {
+            for b in &TARGETS[i + 1..] {
+                assert_ne!(a.triple, b.triple);
+            }
+        }
+    }
+
+    #[test]
+    fn every_target_round_trips() {
+        for t in TARGETS {
+            assert_eq!(UnrealTarget::from_triple(t.triple), Some(*t));
+        }
+    }
+
+    #[test]
+    fn every_platform_has_a_target() {
+        for p in UnrealPlatform::ALL {
+            assert!(TARGETS.iter().any(|t| t.platform == *p), "{p}");
+        }
+    }
+
+    #[test]
+    fn default_targets_are_known() {
+        for triple in DEFAULT_TARGETS {
+            assert!(UnrealTarget::from_triple(triple).is_some(), "{triple}");
+        }
+    }
+
+    #[test]
+    fn unknown_triples() {
+        for triple in ["", "wasm32-unknown-unknown", "x86_64-unknown-freebsd", "aarch64-linux-android "] {
+            assert_eq!(UnrealTarget::from_triple(triple), None);
+        }
+    }
+
+    #[test]
+    fn platforms() {
+        let expect = [
+            ("x86_64-pc-windows-msvc", UnrealPlatform::Win64, UnrealArch::X64),
+            ("aarch64-pc-windows-msvc", UnrealPlatform::Win64, UnrealArch::Arm64),
+            ("x86_64-pc-windows-gnu", UnrealPlatform::Win64, UnrealArch::X64),
+            ("x86_64-pc-windows-gnullvm", UnrealPlatform::Win64, UnrealArch::X64),
+            ("aarch64-pc-windows-gnullvm", UnrealPlatform::Win64, UnrealArch::Arm64),
+            ("x86_64-apple-darwin", UnrealPlatform::Mac, UnrealArch::X64),
+            ("aarch64-apple-darwin", UnrealPlatform::Mac, UnrealArch::Arm64),
+            ("x86_64-unknown-linux-gnu", UnrealPlatform::Linux, UnrealArch::X64),
+            ("aarch64-unknown-linux-gnu", UnrealPlatform::LinuxArm64, UnrealArch::Arm64),
+            ("aarch64-linux-android", UnrealPlatform::Android, UnrealArch::Arm64),
+            ("armv7-linux-androideabi", UnrealPlatform::Android, UnrealArch::Armv7),
+            ("x86_64-linux-android", UnrealPlatform::Android, UnrealArch::X64),
+            ("i686-linux-android", UnrealPlatform::Android, UnrealArch::X86),
+            ("aarch64-apple-ios", UnrealPlatform::IOS, UnrealArch::Arm64),
+            ("aarch64-apple-ios-sim", UnrealPlatform::IOS, UnrealArch::Arm64),
+            ("x86_64-apple-ios", UnrealPlatform::IOS, UnrealArch::X64),
+            ("aarch64-apple-tvos", UnrealPlatform::TVOS, UnrealArch::Arm64),
+            ("aarch64-apple-tvos-sim", UnrealPlatform::TVOS, UnrealArch::Arm64),
+        ];
+        assert_eq!(expect.len(), TARGETS.len());
+        for (triple, platform, arch) in expect {
+            let t = UnrealTarget::from_triple(triple).unwrap();
+            assert_eq!((t.platform, t.arch), (platform, arch), "{triple}");
+        }
+    }
+
+    #[test]
+    fn platform_names() {
+        let names = UnrealPlatform::ALL.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(names, ["Win64", "Mac", "Linux", "LinuxArm64", "Android", "IOS", "TVOS"]);
+    }
+
+    #[test]
+    fn arch_names() {
+        let expect = [
+            ("x86_64-pc-windows-msvc", "x64"),
+            ("aarch64-pc-windows-msvc", "arm64"),
+            ("x86_64-apple-darwin", "x64"),
+            ("aarch64-apple-darwin", "arm64"),
+            ("x86_64-unknown-linux-gnu", "x86_64-unknown-linux-gnu"),
+            ("aarch64-unknown-linux-gnu", "aarch64-unknown-linux-gnueabi"),
+            ("aarch64-linux-android", "arm64-v8a"),
+            ("armv7-linux-androideabi", "armeabi-v7a"),
+            ("x86_64-linux-android", "x86_64"),
+            ("i686-linux-android", "x86"),
+            ("aarch64-apple-ios", "arm64"),
+            ("x86_64-apple-ios", "x64"),
+            ("aarch64-apple-tvos", "arm64"),
+        ];
+        for (triple, arch) in expect {
+            assert_eq!(UnrealTarget::from_triple(triple).unwrap().arch_name(), arch, "{triple}");
+        }
+    }
+
+    #[test]
+    fn stage_dirs() {
+        for t in TARGETS {
+            let components = t.stage_dir_components();
+            assert_eq!(t.stage_dir(), components.iter().collect::<PathBuf>(), "{t}");
+            assert_eq!(components[0], t.platform.name());
+            if t.is_simulator() {
+                assert_eq!(components, [t.platform.name(), "Simulator"]);
+            } else if t.platform.is_apple() {
+                assert_eq!(components.len(), 1);
+            } else {
+                assert_eq!(components, [t.platform.name(), t.arch_name()]);
+            }
+        }
+        let dir = |triple| UnrealTarget::from_triple(triple).unwrap().stage_dir();
+        assert_eq!(dir("aarch64-linux-android"), PathBuf::from("Android").join("arm64-v8a"));
+        assert_eq!(dir("x86_64-pc-windows-msvc"), PathBuf::from("Win64").join("x64"));
+        assert_eq!(dir("aarch64-pc-windows-msvc"), PathBuf::from("Win64").join("arm64"));
+        assert_eq!(dir("x86_64-unknown-linux-gnu"), PathBuf::from("Linux").join("x86_64-unknown-linux-gnu"));
+        assert_eq!(dir("aarch64-unknown-linux-gnu"), PathBuf::from("LinuxArm64").join("aarch64-unknown-linux-gnueabi"));
+        assert_eq!(dir("x86_64-apple-darwin"), dir("aarch64-apple-darwin"));
+        assert_eq!(dir("aarch64-apple-ios"), PathBuf::from("IOS"));
+        assert_eq!(dir("aarch64-apple-ios-sim"), PathBuf::from("IOS").join("Simulator"));
+        assert_eq!(dir("x86_64-apple-ios"), dir("aarch64-apple-ios-sim"));
+        assert_eq!(dir("aarch64-apple-tvos-sim"), PathBuf::from("TVOS").join("Simulator"));
+    }
+
+    #[test]
+    fn library_names() {
+        // (triple, dylib, import lib, staticlib)
+        let expect = [
+            ("x86_64-pc-windows-msvc", "foo.dll", Some("foo.dll.lib"), "foo.lib"),
+            ("aarch64-pc-windows-msvc", "foo.dll", Some("foo.dll.lib"), "foo.lib"),
+            ("x86_64-pc-windows-gnu", "foo.dll", Some("libfoo.dll.a"), "libfoo.a"),
+            ("x86_64-pc-windows-gnullvm", "foo.dll", Some("libfoo.dll.a"), "libfoo.a"),
+            ("aarch64-pc-windows-gnullvm", "foo.dll", Some("libfoo.dll.a"), "libfoo.a"),
+            ("x86_64-apple-darwin", "libfoo.dylib", None, "libfoo.a"),
+            ("aarch64-apple-darwin", "libfoo.dylib", None, "libfoo.a"),
+            ("x86_64-unknown-linux-gnu", "libfoo.so", None, "libfoo.a"),
+            ("aarch64-unknown-linux-gnu", "libfoo.so", None, "libfoo.a"),
+            ("aarch64-linux-android", "libfoo.so", None, "libfoo.a"),
+            ("armv7-linux-androideabi", "libfoo.so", None, "libfoo.a"),
+            ("x86_64-linux-android", "libfoo.so", None, "libfoo.a"),
+            ("i686-linux-android", "libfoo.so", None, "libfoo.a"),
+            ("aarch64-apple-ios", "libfoo.dylib", None, "libfoo.a"),
+            ("aarch64-apple-ios-sim", "libfoo.dylib", None, "libfoo.a"),
+            ("x86_64-apple-ios", "libfoo.dylib", None, "libfoo.a"),
+            ("aarch64-apple-tvos", "libfoo.dylib", None, "libfoo.a"),
+            ("aarch64-apple-tvos-sim", "libfoo.dylib", None, "libfoo.a"),
+        ];
+        assert_eq!(expect.len(), TARGETS.len());
+        for (triple, dylib, import, staticlib) in expect {
+            let t = UnrealTarget::from_triple(triple).unwrap();
+            assert_eq!(t.dylib_filename("foo"), dylib, "{triple}");
+            assert_eq!(t.import_lib_filename("foo").as_deref(), import, "{triple}");
+            assert_eq!(t.staticlib_filename("foo"), staticlib, "{triple}");
+            assert_eq!(t.link_filename("foo"), import.unwrap_or(dylib), "{triple}");
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! `PreBuildSteps` that make UBT rebuild the Rust library before the plugin.
+//!
+//! UBT writes the steps for the host platform into a batch file (Win64) or a
+//! shell script (Mac, Linux), substituting `$(TargetPlatform)`,
+//! `$(TargetConfiguration)` and friends, and runs it before compiling the
+//! plugin's modules.
+
+use super::{profiles::profile_dir, CargoProfiles, UnrealPlatform, UnrealTarget};
+
+/// Runs `cargo build` for the target platform from UBT and copies the
+/// resulting libraries into the staging folders.
+pub struct PreBuildSteps<'a> {
+    /// Directory containing the crate's `Cargo.toml`. May use UBT variables
+    /// such as `$(PluginDir)` or `$(ProjectDir)`.
+    pub crate_dir: &'a str,
+    /// Defaults to `<crate_dir>/target`.
+    pub target_dir: Option<&'a str>,
+    /// Extra arguments for `cargo build`, e.g. `--features`.
+    pub cargo_args: &'a [&'a str],
+    pub hosts: &'a [UnrealPlatform],
+}
+impl<'a> PreBuildSteps<'a> {
+    pub fn new(crate_dir: &'a str) -> Self {
+        Self {
+            crate_dir,
+            target_dir: None,
+            cargo_args: &[],
+            hosts: &[UnrealPlatform::Win64, UnrealPlatform::Mac, UnrealPlatform::Linux],
+        }
+    }
+
+    /// The `"PreBuildSteps"` object for the .uplugin. `modules` lists each
+    /// module's directory relative to the plugin and its external dylibs.
+    /// With `variants`, libraries are copied into a folder per profile.
+    pub(crate) fn to_json(
+        &self,
+        targets: &[UnrealTarget],
+        modules: &[(String, &[&str])],
+        profiles: &CargoProfiles,
+        variants: bool,
+    ) -> String {
+        let hosts = self.hosts
+            .iter()
+            .map(|host| {
+                let lines = self.commands(*host, targets, modules, profiles, variants)
+                    .iter()
+                    .map(|l| format!("            \"{}\"", json_escape(l)))
+                    .collect::<Vec<_>>()
+                    .join(",\n");
+                format!("        \"{}\": [\n{}\n        ]", host, lines)
+            })
+            .collect::<Vec<_>>()
+            .join(",\n");
+        format!("{{\n{}\n    }}", hosts)
+    }
+
+    fn commands(
+        &self,
+        host: UnrealPlatform,
+        targets: &[UnrealTarget],
+        modules: &[(String, &[&str])],
+        profiles: &CargoProfiles,
+        variants: bool,
+    ) -> Vec<String> {
+        let windows = host == UnrealPlatform::Win64;
+        let sep = if windows { "\\" } else { "/" };
+        let target_dir = self.target_dir
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("{}{sep}target", self.crate_dir));
+        let mut cargo = format!("cargo build --manifest-path \"{}{sep}Cargo.toml\" --target-dir \"{}\"", self.crate_dir, target_dir);
+        for arg in self.cargo_args {
+            cargo.push(' ');
+            cargo.push_str(arg);
+        }
+
+        let mut out = vec![];
+        if windows {
+            out.push("set CARGO_PROFILE=".to_string());
+            for c in CargoProfiles::CONFIGURATIONS {
+                let profile = profiles.profile(c);
+                out.push(format!(
+                    "if \"$(TargetConfiguration)\"==\"{c}\" set CARGO_PROFILE={profile}& set CARGO_PROFILE_DIR={}",
+                    profile_dir(profile)
+                ));
+            }
+        } else {
+            out.push("case \"$(TargetConfiguration)\" in".to_string());
+            for c in CargoProfiles::CONFIGURATIONS {
+                let profile = profiles.profile(c);
+                out.push(format!(
+                    "    {c}) CARGO_PROFILE={profile}; CARGO_PROFILE_DIR={} ;;",
+                    profile_dir(profile)
+                ));
+            }
+            out.push("esac".to_string());
+        }
+        let (profile, profile_dir) = if windows {
+            ("%CARGO_PROFILE%", "%CARGO_PROFILE_DIR%")
+        } else {
+            ("$CARGO_PROFILE", "$CARGO_PROFILE_DIR")
+        };
+
+        let mut platforms: Vec<UnrealPlatform> = vec![];
+        for target in targets.iter().filter(|t| builds_on(host, t.platform)) {
+            if !platforms.contains(&target.platform) {
+                platforms.push(target.platform);
+            }
+        }
+        for platform in platforms {
+            let group = targets.iter().filter(|t| t.platform == platform).collect::<Vec<_>>();
+            let mut steps = vec![];
+            for target in &group {
+                steps.push(format!("{cargo} --profile {profile} --target {}", target.triple));
+            }
+            for (module_dir, dylibs) in modules {
+                for dylib in dylibs.iter() {
+                    let built = |t: &UnrealTarget, filename: &str| {
+                        format!("\"{target_dir}{sep}{}{sep}{profile_dir}{sep}{filename}\"", t.triple)
+                    };
+                    let stage_dir = |t: &UnrealTarget| {
+                        let mut dir = t.stage_dir_components();
+                        if variants {
+                            dir.push(profile_dir);
+                        }
+                        format!("$(PluginDir){sep}{}{sep}{}", module_dir.replace('/', sep), dir.join(sep))
+                    };
+                    // targets sharing a folder (Mac arm64 + x64) are merged into one library
+                    let mut folders: Vec<Vec<&UnrealTarget>> = vec![];
+                    for t in &group {
+                        match folders.iter_mut().find(|f| f[0].stage_dir_components() == t.stage_dir_components()) {
+                            Some(folder) => folder.push(t),
+                            None => folders.push(vec![t]),
+                        }
+                    }
+                    for folder in folders {
+                        match folder.as_slice() {
+                            [first, _, ..] if platform.is_apple() => {
+                                let filename = first.dylib_filename(dylib);
+                                let inputs = folder.iter().map(|t| built(t, &filename)).collect::<Vec<_>>().join(" ");
+                                steps.push(format!(
+                                    "mkdir -p \"{dir}\" && lipo -create -output \"{dir}/{filename}\" {inputs}",
+                                    dir = stage_dir(first),
+                                ));
+                            }
+                            _ => {
+                                for t in folder {
+                                    let dir = format!("\"{}\"", stage_dir(t));
+                                    steps.push(if windows {
+                                        format!("(if not exist {dir} mkdir {dir})")
+                                    } else {
+                                        format!("mkdir -p {dir}")
+                                    });
+                                    let files = std::iter::once(t.dylib_filename(dylib)).chain(t.import_lib_filename(dylib));
+                                    for filename in files {
+                                        steps.push(if windows {
+                                            format!("copy /Y {} {dir}", built(t, &filename))
+                                        } else {
+                                            format!("cp {} {dir}", built(t, &filename))
+                                        });
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            out.push(if windows {
+                format!("if \"$(TargetPlatform)\"==\"{platform}\" ({}) || exit /b 1", steps.join(" && "))
+            } else {
+                format!("if [ \"$(TargetPlatform)\" = \"{platform}\" ]; then {} || exit 1; fi", steps.join(" && "))
+            });
+        }
+        out
+    }
+}
+
+/// Whether UBT can build `platform` on `host`, and so might run the steps for it.
+fn builds_on(host: UnrealPlatform, platform: UnrealPlatform) -> bool {
+    use UnrealPlatform::*;
+    match host {
+        Win64 => matches!(platform, Win64 | Android | Linux | LinuxArm64),
+        Mac => matches!(platform, Mac | IOS | TVOS | Android),
+        Linux => matches!(platform, Linux | LinuxArm64 | Android),
+        _ => false,
+    }
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(triples: &[&str]) -> Vec<UnrealTarget> {
+        triples.iter().map(|t| UnrealTarget::from_triple(t).unwrap()).collect()
+    }
+
+    #[test]
+    fn windows_hosts_run_batch() {
+        let steps = PreBuildSteps { cargo_args: &["--features", "ue"], ..PreBuildSteps::new("Rust") };
+        let targets = targets(&["x86_64-pc-windows-msvc", "aarch64-linux-android", "aarch64-apple-darwin"]);
+        let modules = [("Source".to_string(), &["foo"][..])];
+        let commands = steps.commands(UnrealPlatform::Win64, &targets, &modules, &CargoProfiles::default(), false);
+        assert_eq!(commands[..3], [
+            "set CARGO_PROFILE=",
+            "if \"$(TargetConfiguration)\"==\"Debug\" set CARGO_PROFILE=dev& set CARGO_PROFILE_DIR=debug",
+            "if \"$(TargetConfiguration)\"==\"DebugGame\" set CARGO_PROFILE=dev& set CARGO_PROFILE_DIR=debug",
+        ]);
+        assert_eq!(commands[6], [
+            "if \"$(TargetPlatform)\"==\"Win64\" (",
+            "cargo build --manifest-path \"Rust\\Cargo.toml\" --target-dir \"Rust\\target\" --features ue --profile %CARGO_PROFILE% --target x86_64-pc-windows-msvc",
+            " && (if not exist \"$(PluginDir)\\Source\\Win64\\x64\" mkdir \"$(PluginDir)\\Source\\Win64\\x64\")",
+            " && copy /Y \"Rust\\target\\x86_64-pc-windows-msvc\\%CARGO_PROFILE_DIR%\\foo.dll\" \"$(PluginDir)\\Source\\Win64\\x64\"",
+            " && copy /Y \"Rust\\target\\x86_64-pc-windows-msvc\\%CARGO_PROFILE_DIR%\\foo.dll.lib\" \"$(PluginDir)\\Source\\Win64\\x64\"",
+            ") || exit /b 1",
+        ].concat());
+        assert!(commands[7].starts_with("if \"$(TargetPlatform)\"==\"Android\" (cargo build "), "{}", commands[7]);
+        // Mac libraries can't be built on Windows
+        assert_eq!(commands.len(), 8);
+    }
+
+    #[test]
+    fn unix_hosts_run_sh() {
+        let steps = PreBuildSteps { target_dir: Some("/tmp/target"), ..PreBuildSteps::new("Rust") };
+        let targets = targets(&["x86_64-pc-windows-msvc", "aarch64-linux-android"]);
+        let modules = [("Source/Foo".to_string(), &["foo"][..])];
+        let profiles = CargoProfiles { development: "dev", ..Default::default() };
+        let commands = steps.commands(UnrealPlatform::Linux, &targets, &modules, &profiles, true);
+        assert_eq!(commands[..7], [
+            "case \"$(TargetConfiguration)\" in",
+            "    Debug) CARGO_PROFILE=dev; CARGO_PROFILE_DIR=debug ;;",
+            "    DebugGame) CARGO_PROFILE=dev; CARGO_PROFILE_DIR=debug ;;",
+            "    Development) CARGO_PROFILE=dev; CARGO_PROFILE_DIR=debug ;;",
+            "    Test) CARGO_PROFILE=release; CARGO_PROFILE_DIR=release ;;",
+            "    Shipping) CARGO_PROFILE=release; CARGO_PROFILE_DIR=release ;;",
+            "esac",
+        ]);
+        // Win64 libraries can't be built on Linux, libraries go into a folder per profile
+        assert_eq!(commands[7..], [[
+            "if [ \"$(TargetPlatform)\" = \"Android\" ]; then ",
+            "cargo build --manifest-path \"Rust/Cargo.toml\" --target-dir \"/tmp/target\" --profile $CARGO_PROFILE --target aarch64-linux-android",
+            " && mkdir -p \"$(PluginDir)/Source/Foo/Android/arm64-v8a/$CARGO_PROFILE_DIR\"",
+            " && cp \"/tmp/target/aarch64-linux-android/$CARGO_PROFILE_DIR/libfoo.so\" \"$(PluginDir)/Source/Foo/Android/arm64-v8a/$CARGO_PROFILE_DIR\"",
+            " || exit 1; fi",
+        ].concat()]);
+    }
+
+    #[test]
+    fn mac_architectures_are_merged_with_lipo() {
+        let steps = PreBuildSteps::new("Rust");
+        let targets = targets(&["aarch64-apple-darwin", "x86_64-apple-darwin", "aarch64-apple-ios"]);
+        let modules = [("Source".to_string(), &["foo"][..])];
+        let commands = steps.commands(UnrealPlatform::Mac, &targets, &modules, &CargoProfiles::default(), false);
+        assert_eq!(commands[7], [
+            "if [ \"$(TargetPlatform)\" = \"Mac\" ]; then ",
+            "cargo build --manifest-path \"Rust/Cargo.toml\" --target-dir \"Rust/target\" --profile $CARGO_PROFILE --target aarch64-apple-darwin",
+            " && cargo build --manifest-path \"Rust/Cargo.toml\" --target-dir \"Rust/target\" --profile $CARGO_PROFILE --target x86_64-apple-darwin",
+            " && mkdir -p \"$(PluginDir)/Source/Mac\"",
+            " && lipo -create -output \"$(PluginDir)/Source/Mac/libfoo.dylib\"",
+            " \"Rust/target/aarch64-apple-darwin/$CARGO_PROFILE_DIR/libfoo.dylib\" \"Rust/target/x86_64-apple-darwin/$CARGO_PROFILE_DIR/libfoo.dylib\"",
+            " || exit 1; fi",
+        ].concat());
+        // a single architecture is copied
+        assert!(commands[8].contains(" && cp \"Rust/target/aarch64-apple-ios/$CARGO_PROFILE_DIR/libfoo.dylib\" \"$(PluginDir)/Source/IOS\""), "{}", commands[8]);
+    }
+
+    #[test]
+    fn json_has_a_list_per_host() {
+        let steps = PreBuildSteps { hosts: &[UnrealPlatform::Win64, UnrealPlatform::Mac], ..PreBuildSteps::new("Rust") };
+        let json = steps.to_json(&targets(&["x86_64-pc-windows-msvc"]), &[], &CargoProfiles::default(), false);
+        assert!(json.starts_with("{\n        \"Win64\": [\n            \"set CARGO_PROFILE=\",\n"), "{json}");
+        assert!(json.contains("\"if \\\"$(TargetConfiguration)\\\"==\\\"Debug\\\" set CARGO_PROFILE=dev& set CARGO_PROFILE_DIR=debug\","), "{json}");
+        assert!(json.contains("--manifest-path \\\"Rust\\\\Cargo.toml\\\""), "{json}");
+        assert!(json.ends_with("            \"esac\"\n        ]\n    }"), "{json}");
+    }
+}
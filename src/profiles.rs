@@ -0,0 +1,76 @@
+//! Mapping from UBT configurations to cargo profiles.
+//!
+//! With library variants enabled, every profile's libraries are staged into
+//! their own folder (`Win64/x64/debug`, `Win64/x64/release`, ...) and the generated
+//! build.cs, APL and pre-build steps pick the folder for the configuration
+//! being built.
+
+use super::TargetConfiguration;
+
+/// Cargo profile used for each UBT configuration.
+#[derive(Debug, Clone, Copy)]
+pub struct CargoProfiles<'a> {
+    pub debug: &'a str,
+    pub debug_game: &'a str,
+    pub development: &'a str,
+    pub test: &'a str,
+    pub shipping: &'a str,
+}
+impl<'a> Default for CargoProfiles<'a> {
+    fn default() -> Self {
+        Self {
+            debug: "dev",
+            debug_game: "dev",
+            development: "release",
+            test: "release",
+            shipping: "release",
+        }
+    }
+}
+impl<'a> CargoProfiles<'a> {
+    pub const CONFIGURATIONS: [TargetConfiguration; 5] = [
+        TargetConfiguration::Debug,
+        TargetConfiguration::DebugGame,
+        TargetConfiguration::Development,
+        TargetConfiguration::Test,
+        TargetConfiguration::Shipping,
+    ];
+
+    pub fn profile(&self, configuration: TargetConfiguration) -> &'a str {
+        match configuration {
+            TargetConfiguration::Debug => self.debug,
+            TargetConfiguration::DebugGame => self.debug_game,
+            TargetConfiguration::Development => self.development,
+            TargetConfiguration::Test => self.test,
+            TargetConfiguration::Shipping => self.shipping,
+        }
+    }
+
+    /// The staging folder for `configuration`.
+    pub fn dir(&self, configuration: TargetConfiguration) -> &'a str {
+        profile_dir(self.profile(configuration))
+    }
+
+    /// Distinct staging folders with the configurations that use them. The
+    /// first one is the `Development` folder, used as the fallback.
+    pub fn dirs(&self) -> Vec<(&'a str, Vec<TargetConfiguration>)> {
+        let mut out: Vec<(&'a str, Vec<TargetConfiguration>)> = vec![(self.dir(TargetConfiguration::Development), vec![])];
+        for c in Self::CONFIGURATIONS {
+            let dir = self.dir(c);
+            match out.iter_mut().find(|(d, _)| *d == dir) {
+                Some((_, configs)) => configs.push(c),
+                None => out.push((dir, vec![c])),
+            }
+        }
+        out
+    }
+}
+
+/// Folder under `target/<triple>` cargo writes a profile's artifacts to.
+pub fn profile_dir(profile: &str) -> &str {
+    match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        p => p,
+    }
+}
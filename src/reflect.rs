@@ -0,0 +1,289 @@
+//! `USTRUCT`/`UENUM` mirrors of a crate's `#[repr(C)]` types, with
+//! conversions to and from the C layout.
+
+use super::{
+    capi::{push_docs, CApi, CEnum, CStruct, CType},
+    wrappers::{blueprint_type, pascal_case},
+    Error, Result,
+};
+
+/// A `#[repr(C)]` type mirrored as a reflected UE type.
+enum Reflected<'a> {
+    Struct(&'a CStruct),
+    Enum(&'a CEnum),
+}
+impl Reflected<'_> {
+    fn name(&self) -> &str {
+        match self {
+            Reflected::Struct(s) => &s.name,
+            Reflected::Enum(e) => &e.name,
+        }
+    }
+}
+
+pub(crate) struct ReflectedTypes<'a> {
+    api: &'a CApi,
+    types: Vec<Reflected<'a>>,
+    module_name: &'a str,
+    api_macro: String,
+}
+
+/// How a field is stored in the `USTRUCT` and converted.
+struct Field {
+    /// UE declaration of the `UPROPERTY`.
+    decl: String,
+    from_rust: String,
+    to_rust: String,
+}
+
+/// A UE type standing in for a C type.
+struct Element {
+    ue: String,
+    from_rust: Box<dyn Fn(&str) -> String>,
+    to_rust: Box<dyn Fn(&str) -> String>,
+}
+impl Element {
+    fn same(ue: String) -> Self {
+        Element { ue, from_rust: Box::new(|v| v.to_string()), to_rust: Box::new(|v| v.to_string()) }
+    }
+}
+
+impl<'a> ReflectedTypes<'a> {
+    /// The listed structs and enums of `api`, by Rust name.
+    pub(crate) fn new(api: &'a CApi, names: &[&str], module_name: &'a str) -> Result<Self> {
+        let types = names
+            .iter()
+            .map(|name| {
+                if let Some(s) = api.structs.iter().find(|s| s.name == *name) {
+                    Ok(Reflected::Struct(s))
+                } else if let Some(e) = api.enums.iter().find(|e| e.name == *name) {
+                    Ok(Reflected::Enum(e))
+                } else {
+                    Err(Error::UnsupportedType(format!("{}: not a #[repr(C)] struct or enum", name)))
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { api, types, module_name, api_macro: format!("{}_API", module_name.to_uppercase()) })
+    }
+
+    /// `F<Module><Name>` or `E<Module><Name>`, so no engine type is redefined.
+    fn ue_name(&self, t: &Reflected) -> String {
+        match t {
+            Reflected::Struct(s) => format!("F{}{}", self.module_name, s.name),
+            Reflected::Enum(e) => format!("E{}{}", self.module_name, e.name),
+        }
+    }
+
+    fn find(&self, name: &str) -> Option<&Reflected<'a>> {
+        self.types.iter().find(|t| t.name() == name)
+    }
+
+    /// `Public/<Module>Types.h`
+    pub(crate) fn header(&self) -> Result<String> {
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+            format!("#include \"{}RustApi.h\"", self.module_name),
+            format!("#include \"{}Types.generated.h\"", self.module_name),
+        ];
+        // UHT needs enums and nested structs declared before use
+        let enums = self.types.iter().filter_map(|t| match t {
+            Reflected::Enum(e) => Some(e),
+            _ => None,
+        });
+        for e in enums {
+            let ue_name = self.ue_name(&Reflected::Enum(e));
+            out.push(String::new());
+            push_docs(&mut out, &e.docs, "");
+            out.push("UENUM(BlueprintType)".to_string());
+            out.push(format!("enum class {} : uint8", ue_name));
+            out.push("{".to_string());
+            let mut next = 0;
+            for (variant, value) in &e.variants {
+                let value = value.unwrap_or(next);
+                if !(0..=255).contains(&value) {
+                    return Err(Error::UnsupportedType(format!("{}::{}: UENUM values must fit in uint8", e.name, variant)));
+                }
+                out.push(format!("    {} = {},", variant, value));
+                next = value + 1;
+            }
+            out.push("};".to_string());
+            out.push(String::new());
+            out.push(format!("inline {} ToRust({} Value) {{ return static_cast<{}>(Value); }}", e.name, ue_name, e.name));
+            out.push(format!("inline {} FromRust({} Value) {{ return static_cast<{}>(Value); }}", ue_name, e.name, ue_name));
+        }
+        for s in self.structs_in_order() {
+            let ue_name = self.ue_name(&Reflected::Struct(s));
+            out.push(String::new());
+            push_docs(&mut out, &s.docs, "");
+            out.push("USTRUCT(BlueprintType)".to_string());
+            out.push(format!("struct {} {}", self.api_macro, ue_name));
+            out.push("{".to_string());
+            out.push("    GENERATED_BODY()".to_string());
+            for (name, ty) in &s.fields {
+                let field = self.field(s, name, ty)?;
+                out.push(String::new());
+                out.push("    UPROPERTY(EditAnywhere, BlueprintReadWrite)".to_string());
+                out.push(format!("    {};", field.decl));
+            }
+            out.push(String::new());
+            out.push(format!("    static {} FromRust(const {}& Value);", ue_name, s.name));
+            out.push(format!("    {} ToRust() const;", s.name));
+            out.push("};".to_string());
+        }
+        out.push(String::new());
+        Ok(out.join("\n"))
+    }
+
+    /// `Private/<Module>Types.cpp`
+    pub(crate) fn source(&self) -> Result<String> {
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}Types.h\"", self.module_name),
+        ];
+        for s in self.structs_in_order() {
+            let ue_name = self.ue_name(&Reflected::Struct(s));
+            let fields = s.fields
+                .iter()
+                .map(|(name, ty)| self.field(s, name, ty))
+                .collect::<Result<Vec<_>>>()?;
+            out.push(String::new());
+            out.push(format!("{} {}::FromRust(const {}& Value) {{", ue_name, ue_name, s.name));
+            out.push(format!("    {} Out;", ue_name));
+            for field in &fields {
+                out.push(format!("    {}", field.from_rust));
+            }
+            out.push("    return Out;".to_string());
+            out.push("}".to_string());
+            out.push(String::new());
+            out.push(format!("{} {}::ToRust() const {{", s.name, ue_name));
+            out.push(format!("    {} Out = {{}};", s.name));
+            for field in &fields {
+                out.push(format!("    {}", field.to_rust));
+            }
+            out.push("    return Out;".to_string());
+            out.push("}".to_string());
+        }
+        out.push(String::new());
+        Ok(out.join("\n"))
+    }
+
+    /// The reflected structs, after the structs they contain.
+    fn structs_in_order(&self) -> Vec<&'a CStruct> {
+        self.api
+            .structs_in_declaration_order()
+            .into_iter()
+            .filter(|s| self.find(&s.name).is_some())
+            .collect()
+    }
+
+    /// The Blueprint type of a value of `ty`, with expressions converting
+    /// from Rust to UE and back.
+    fn element(&self, ty: &CType) -> Option<Element> {
+        match ty {
+            CType::Named(name) => {
+                let reflected = self.find(name)?;
+                let ue = self.ue_name(reflected);
+                Some(match reflected {
+                    Reflected::Struct(_) => Element {
+                        ue: ue.clone(),
+                        from_rust: Box::new(move |v| format!("{}::FromRust({})", ue, v)),
+                        to_rust: Box::new(|v| format!("{}.ToRust()", v)),
+                    },
+                    Reflected::Enum(_) => Element {
+                        ue,
+                        // qualified, the struct's own FromRust/ToRust hide them
+                        from_rust: Box::new(|v| format!("::FromRust({})", v)),
+                        to_rust: Box::new(|v| format!("::ToRust({})", v)),
+                    },
+                })
+            }
+            CType::Ptr { .. } | CType::Array(..) | CType::FnPtr { .. } | CType::Void => None,
+            ty => {
+                let c_type = ty.declare("");
+                Some(match blueprint_type(ty)? {
+                    bp if bp == c_type => Element::same(c_type),
+                    // widened for Blueprint
+                    bp => Element {
+                        ue: bp.to_string(),
+                        from_rust: Box::new(move |v| format!("static_cast<{}>({})", bp, v)),
+                        to_rust: Box::new(move |v| format!("static_cast<{}>({})", c_type, v)),
+                    },
+                })
+            }
+        }
+    }
+
+    fn field(&self, s: &CStruct, name: &str, ty: &CType) -> Result<Field> {
+        let unsupported = || Error::UnsupportedType(format!("{}::{}: not a Blueprint type", s.name, name));
+        let mut var = pascal_case(name);
+        if *ty == CType::Bool {
+            var = format!("b{}", var);
+        }
+        match ty {
+            CType::Array(elem, len) => {
+                let elem = self.element(elem).ok_or_else(unsupported)?;
+                Ok(Field {
+                    decl: format!("TArray<{}> {}", elem.ue, var),
+                    from_rust: format!(
+                        "for (int32 i = 0; i < {len}; ++i) {{ Out.{var}.Add({}); }}",
+                        (elem.from_rust)(&format!("Value.{}[i]", name))
+                    ),
+                    to_rust: format!(
+                        "for (int32 i = 0; i < FMath::Min({var}.Num(), {len}); ++i) {{ Out.{name}[i] = {}; }}",
+                        (elem.to_rust)(&format!("{}[i]", var))
+                    ),
+                })
+            }
+            ty => {
+                let elem = self.element(ty).ok_or_else(unsupported)?;
+                Ok(Field {
+                    decl: format!("{} {}", elem.ue, var),
+                    from_rust: format!("Out.{} = {};", var, (elem.from_rust)(&format!("Value.{}", name))),
+                    to_rust: format!("Out.{} = {};", name, (elem.to_rust)(&var)),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api(name: &str, fields: &[(&str, CType)]) -> CApi {
+        let fields = fields.iter().map(|(n, ty)| (n.to_string(), ty.clone())).collect();
+        CApi { structs: vec![CStruct { name: name.to_string(), fields, docs: vec![] }], ..CApi::default() }
+    }
+
+    #[test]
+    fn blueprint_fields() {
+        let api = api("Point", &[("x", CType::Float), ("id", CType::UInt32), ("visible", CType::Bool)]);
+        let header = ReflectedTypes::new(&api, &["Point"], "Foo").unwrap().header().unwrap();
+        assert!(header.contains("    float X;\n"), "{header}");
+        assert!(header.contains("    int64 Id;\n"), "{header}");
+        assert!(header.contains("    bool bVisible;\n"), "{header}");
+    }
+
+    #[test]
+    fn non_blueprint_fields() {
+        for ty in [CType::Char, CType::Array(Box::new(CType::Char), 8), CType::Ptr { ty: Box::new(CType::Float), is_const: true }] {
+            let api = api("Point", &[("x", ty.clone())]);
+            let types = ReflectedTypes::new(&api, &["Point"], "Foo").unwrap();
+            assert!(matches!(types.header(), Err(Error::UnsupportedType(_))), "{ty:?}");
+        }
+    }
+
+    #[test]
+    fn types_are_prefixed_with_the_module() {
+        // FVector would redefine the engine's
+        let api = api("Vector", &[("x", CType::Float)]);
+        let header = ReflectedTypes::new(&api, &["Vector"], "Foo").unwrap().header().unwrap();
+        assert!(header.contains("struct FOO_API FFooVector"), "{header}");
+        assert!(header.contains("    static FFooVector FromRust(const Vector& Value);"), "{header}");
+    }
+}
@@ -0,0 +1,299 @@
+//! `UDeveloperSettings` shown in Project Settings, with defaults in the
+//! plugin's `Config/Default<Plugin>.ini` and accessors handed to Rust.
+
+use super::{
+    ueplugingen::c_escape,
+    wrappers::{pascal_case, snake_case},
+};
+use serde::Serialize;
+
+/// The type and default value of a setting.
+#[derive(Debug, Clone, Copy)]
+pub enum SettingValue<'a> {
+    Bool(bool),
+    Int(i32),
+    Float(f32),
+    String(&'a str),
+}
+impl SettingValue<'_> {
+    pub(crate) fn cpp_type(self) -> &'static str {
+        match self {
+            SettingValue::Bool(_) => "bool",
+            SettingValue::Int(_) => "int32",
+            SettingValue::Float(_) => "float",
+            SettingValue::String(_) => "FString",
+        }
+    }
+
+    pub(crate) fn cpp_literal(self) -> String {
+        match self {
+            SettingValue::Bool(b) => b.to_string(),
+            SettingValue::Int(i) => i.to_string(),
+            SettingValue::Float(f) => format!("{:?}f", f),
+            SettingValue::String(s) => format!("TEXT(\"{}\")", c_escape(s)),
+        }
+    }
+
+    fn ini_value(self) -> String {
+        match self {
+            SettingValue::Bool(true) => "True".to_string(),
+            SettingValue::Bool(false) => "False".to_string(),
+            SettingValue::Int(i) => i.to_string(),
+            SettingValue::Float(f) => format!("{:?}", f),
+            SettingValue::String(s) => format!("\"{}\"", ini_escape(s)),
+        }
+    }
+}
+
+/// Escapes `s` for a quoted ini value, which `FParse::QuotedString`
+/// unescapes when the config is read; a raw line break would end the line.
+fn ini_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Given to templates as the default value itself.
+impl Serialize for SettingValue<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match *self {
+            SettingValue::Bool(b) => serializer.serialize_bool(b),
+            SettingValue::Int(i) => serializer.serialize_i32(i),
+            SettingValue::Float(f) => serializer.serialize_f32(f),
+            SettingValue::String(s) => serializer.serialize_str(s),
+        }
+    }
+}
+
+/// A `UPROPERTY(config)` of the module's settings class.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Setting<'a> {
+    /// The property name, e.g. `MaxConnections` or `bVerbose`.
+    pub name: &'a str,
+    pub value: SettingValue<'a>,
+    pub category: &'a str,
+    pub tooltip: &'a str,
+}
+
+/// `U<Module>Settings : UDeveloperSettings`, saved to the
+/// `[/Script/<Module>.<Module>Settings]` section of `Default<Plugin>.ini`.
+///
+/// With `rust_settings`, `StartupModule` calls
+/// `extern "C" fn <rust_settings>(settings: *const RustSettings)` with a
+/// `#[repr(C)]` struct holding one accessor per setting, in order, and
+/// `ShutdownModule` calls it with null. Accessors are
+/// `extern "C" fn() -> bool/i32/f32`, or for strings
+/// `extern "C" fn(buf: *mut c_char, len: usize) -> usize`, which copies up
+/// to `len` bytes of UTF-8 and returns the full length.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct DeveloperSettings<'a> {
+    /// Shown in Project Settings > Plugins, defaults to the module name.
+    pub display_name: Option<&'a str>,
+    pub settings: &'a [Setting<'a>],
+    /// The Rust function the accessors are handed to.
+    pub rust_settings: Option<&'a str>,
+}
+impl DeveloperSettings<'_> {
+    /// The struct of accessors handed to `rust_settings`.
+    pub(crate) fn accessors(module_name: &str) -> String {
+        format!("F{}RustSettings", module_name)
+    }
+
+    /// The function returning the accessors.
+    pub(crate) fn accessors_getter(module_name: &str) -> String {
+        format!("{}RustSettings", module_name)
+    }
+
+    /// The accessor's field name, a leading `b` of bool settings dropped.
+    fn field(&self, setting: &Setting) -> String {
+        let name = match setting.value {
+            SettingValue::Bool(_) => strip_bool_prefix(setting.name),
+            _ => setting.name,
+        };
+        snake_case(name).trim_start_matches('_').to_string()
+    }
+
+    /// The C++ function behind the accessor.
+    fn getter(&self, setting: &Setting) -> String {
+        format!("Get{}", pascal_case(&self.field(setting)))
+    }
+
+    fn accessor_decl(&self, setting: &Setting, name: &str) -> String {
+        match setting.value {
+            SettingValue::String(_) => format!("SIZE_T {}(ANSICHAR* Buffer, SIZE_T Len)", name),
+            value => format!("{} {}()", value.cpp_type(), name),
+        }
+    }
+
+    /// `Public/<Module>Settings.h`
+    pub(crate) fn header(&self, plugin_name: &str, module_name: &str) -> String {
+        let api_macro = format!("{}_API", module_name.to_uppercase());
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+            "#include \"Engine/DeveloperSettings.h\"".to_string(),
+            format!("#include \"{}Settings.generated.h\"", module_name),
+            String::new(),
+            // the config name picks Default<Plugin>.ini, where the defaults are
+            format!(
+                "UCLASS(config = {}, defaultconfig, meta = (DisplayName = \"{}\"))",
+                plugin_name,
+                c_escape(self.display_name.unwrap_or(module_name))
+            ),
+            format!("class {} U{}Settings : public UDeveloperSettings", api_macro, module_name),
+            "{".to_string(),
+            "    GENERATED_BODY()".to_string(),
+            String::new(),
+            "public:".to_string(),
+            format!("    U{}Settings();", module_name),
+            String::new(),
+            "    virtual FName GetCategoryName() const override { return TEXT(\"Plugins\"); }".to_string(),
+        ];
+        for setting in self.settings {
+            out.push(String::new());
+            if !setting.tooltip.is_empty() {
+                out.push(format!("    /** {} */", setting.tooltip.replace("*/", "* /")));
+            }
+            out.push(format!("    UPROPERTY(config, EditAnywhere, Category = \"{}\")", c_escape(setting.category)));
+            out.push(format!("    {} {};", setting.value.cpp_type(), setting.name));
+        }
+        out.push("};".to_string());
+        if self.rust_settings.is_some() {
+            out.push(String::new());
+            out.push("/** Reads the settings for Rust, one function per setting in declaration order. */".to_string());
+            out.push(format!("struct {}", Self::accessors(module_name)));
+            out.push("{".to_string());
+            for setting in self.settings {
+                out.push(format!("    {};", self.accessor_decl(setting, &format!("(*{})", self.field(setting)))));
+            }
+            out.push("};".to_string());
+            out.push(String::new());
+            out.push(format!("{} const {}* {}();", api_macro, Self::accessors(module_name), Self::accessors_getter(module_name)));
+        }
+        out.push(String::new());
+        out.join("\n")
+    }
+
+    /// `Private/<Module>Settings.cpp`
+    pub(crate) fn source(&self, module_name: &str) -> String {
+        let class = format!("U{}Settings", module_name);
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}Settings.h\"", module_name),
+            String::new(),
+            format!("{}::{}() {{", class, class),
+        ];
+        for setting in self.settings {
+            out.push(format!("    {} = {};", setting.name, setting.value.cpp_literal()));
+        }
+        out.push("}".to_string());
+        if self.rust_settings.is_none() {
+            out.push(String::new());
+            return out.join("\n");
+        }
+        out.push(String::new());
+        out.push("namespace {".to_string());
+        for (i, setting) in self.settings.iter().enumerate() {
+            if i > 0 {
+                out.push(String::new());
+            }
+            out.push(format!("{} {{", self.accessor_decl(setting, &self.getter(setting))));
+            match setting.value {
+                SettingValue::String(_) => {
+                    out.push(format!("    const FTCHARToUTF8 Utf8(*GetDefault<{}>()->{});", class, setting.name));
+                    out.push("    if (Buffer) {".to_string());
+                    out.push("        FMemory::Memcpy(Buffer, Utf8.Get(), FMath::Min<SIZE_T>(Len, Utf8.Length()));".to_string());
+                    out.push("    }".to_string());
+                    out.push("    return Utf8.Length();".to_string());
+                }
+                _ => out.push(format!("    return GetDefault<{}>()->{};", class, setting.name)),
+            }
+            out.push("}".to_string());
+        }
+        out.push("}".to_string());
+        out.push(String::new());
+        let accessors = Self::accessors(module_name);
+        out.push(format!("const {}* {}() {{", accessors, Self::accessors_getter(module_name)));
+        out.push(format!("    static const {} Accessors = {{", accessors));
+        for setting in self.settings {
+            out.push(format!("        &{},", self.getter(setting)));
+        }
+        out.push("    };".to_string());
+        out.push("    return &Accessors;".to_string());
+        out.push("}".to_string());
+        out.push(String::new());
+        out.join("\n")
+    }
+
+    /// The `Default<Plugin>.ini` section holding the defaults.
+    pub(crate) fn ini_section(&self, module_name: &str) -> String {
+        format!("/Script/{}.{}Settings", module_name, module_name)
+    }
+
+    pub(crate) fn ini_lines(&self) -> Vec<String> {
+        self.settings
+            .iter()
+            .map(|setting| format!("{}={}", setting.name, setting.value.ini_value()))
+            .collect()
+    }
+}
+
+/// `bVerbose` -> `Verbose`
+fn strip_bool_prefix(name: &str) -> &str {
+    match name.strip_prefix('b') {
+        Some(rest) if rest.starts_with(|c: char| c.is_ascii_uppercase()) => rest,
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SETTINGS: DeveloperSettings = DeveloperSettings {
+        display_name: Some("My \"Plugin\""),
+        settings: &[
+            Setting { name: "bVerbose", value: SettingValue::Bool(true), category: "Debug", tooltip: "" },
+            Setting { name: "ServerUrl", value: SettingValue::String("a\\b"), category: "Network", tooltip: "" },
+        ],
+        rust_settings: Some("foo_settings"),
+    };
+
+    #[test]
+    fn header() {
+        let header = SETTINGS.header("FooPlugin", "Foo");
+        assert!(header.contains("UCLASS(config = FooPlugin, defaultconfig, meta = (DisplayName = \"My \\\"Plugin\\\"\"))"), "{header}");
+        assert!(header.contains("struct FFooRustSettings\n{\n    bool (*verbose)();\n    SIZE_T (*server_url)(ANSICHAR* Buffer, SIZE_T Len);\n};"), "{header}");
+        assert!(!header.contains("extern \"C\""), "{header}");
+    }
+
+    #[test]
+    fn source() {
+        let source = SETTINGS.source("Foo");
+        assert!(source.contains("    ServerUrl = TEXT(\"a\\\\b\");"), "{source}");
+        assert!(source.contains("    static const FFooRustSettings Accessors = {\n        &GetVerbose,\n        &GetServerUrl,\n    };"), "{source}");
+    }
+
+    #[test]
+    fn ini_lines() {
+        assert_eq!(SETTINGS.ini_lines(), ["bVerbose=True", "ServerUrl=\"a\\\\b\""]);
+        let settings = DeveloperSettings {
+            settings: &[Setting { name: "Motd", value: SettingValue::String("say \"hi\"\\\n\tbye"), category: "", tooltip: "" }],
+            ..Default::default()
+        };
+        assert_eq!(settings.ini_lines(), [r#"Motd="say \"hi\"\\\n\tbye""#]);
+    }
+}
@@ -0,0 +1,105 @@
+//! User templates replacing the built-in ones at generation time.
+//!
+//! Overrides are rendered at runtime with minijinja, with the same context
+//! as the built-in askama templates.
+
+use super::{
+    logging::LogVerbosity,
+    platform::UnrealPlatform,
+    ueplugingen::{HostType, LoadingPhase, TargetConfiguration},
+    Error, Result,
+};
+use minijinja::{
+    escape_formatter,
+    value::{Serde, ValueKind},
+    AutoEscape, Environment, UndefinedBehavior, Value,
+};
+use serde::Serialize;
+use std::path::Path;
+
+/// A directory of templates named like the built-in ones, e.g.
+/// `DefaultModule.cpp.jinja`. A file found there is used instead of the
+/// built-in template and rendered as a jinja template with the context
+/// listed in the README; `{{ builtin }}` is the built-in output, so a
+/// template can wrap it instead of starting over.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TemplateOverrides<'a> {
+    pub dir: Option<&'a Path>,
+}
+impl TemplateOverrides<'_> {
+    /// The user's `name` template rendered with `template` as context, else
+    /// the built-in one.
+    pub(crate) fn render<T: askama::Template + Serialize>(&self, name: &str, template: &T) -> Result<String> {
+        self.render_with(name, template.render()?, template)
+    }
+
+    /// The user's `name` template rendered with `context`, else `builtin`.
+    pub(crate) fn render_with(&self, name: &str, builtin: String, context: &impl Serialize) -> Result<String> {
+        let Some(path) = self.dir.map(|dir| dir.join(name)).filter(|path| path.is_file()) else {
+            return Ok(builtin);
+        };
+        let template = std::fs::read_to_string(&path)?;
+        let mut env = Environment::new();
+        // like the built-in templates, which are rendered with `escape = "none"`
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        // `true`, not python's `True`, for .uplugin JSON and C++
+        env.set_formatter(|out, state, value| match value.kind() {
+            ValueKind::Bool => out.write_str(if value.is_true() { "true" } else { "false" }).map_err(Into::into),
+            _ => escape_formatter(out, state, value),
+        });
+        let context = minijinja::context! { builtin => builtin, ..Value::from(Serde(context)) };
+        env.render_str(&template, context).map_err(|why| Error::InvalidTemplate(format!("{}: {:#}", path.display(), why)))
+    }
+}
+
+/// Types given to templates as their `Display` text.
+macro_rules! serialize_display {
+    ($($ty:ty),*) => {$(
+        impl Serialize for $ty {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+    )*};
+}
+serialize_display!(UnrealPlatform, TargetConfiguration, HostType, LoadingPhase, LogVerbosity);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Context<'a> {
+        name: &'a str,
+        items: Vec<(&'a str, bool)>,
+        init: Option<&'a str>,
+    }
+
+    fn render(template: &str) -> Result<String> {
+        let dir = std::env::temp_dir().join(format!("ueplugingen-templates-{}-{}", std::process::id(), template.len()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Foo.h.jinja"), template).unwrap();
+        let context = Context { name: "Foo", items: vec![("a", true), ("b", false)], init: Some("foo_init") };
+        let rendered = TemplateOverrides { dir: Some(&dir) }.render_with("Foo.h.jinja", "#pragma once".to_string(), &context);
+        std::fs::remove_dir_all(dir).unwrap();
+        rendered
+    }
+
+    #[test]
+    fn overrides() {
+        assert_eq!(render("// {{ name }}\n{{ builtin }}\n").unwrap(), "// Foo\n#pragma once");
+        assert_eq!(render("{% for item, enabled in items if enabled %}{{ item }}{% endfor %}{% if init %} {{ init }}();{% endif %}").unwrap(), "a foo_init();");
+        assert_eq!(render("{{ name | upper }} <{{ items | length }}> {{ items[0][1] }}").unwrap(), "FOO <2> true");
+        let templates = TemplateOverrides { dir: Some(Path::new("/nonexistent")) };
+        assert_eq!(templates.render_with("Foo.h.jinja", "#pragma once".to_string(), &()).unwrap(), "#pragma once");
+    }
+
+    #[test]
+    fn errors() {
+        for template in ["{{ nope }}", "{% if name %}", "{{ builtin"] {
+            let err = render(template).unwrap_err();
+            assert!(matches!(&err, Error::InvalidTemplate(why) if why.contains("Foo.h.jinja")), "{template}: {err:?}");
+        }
+    }
+}
@@ -1,5 +1,21 @@
 use askama::Template;
-use super::Result;
+use serde::Serialize;
+use super::{
+    android::{java_source_path, xml_escape, AndroidConfig, JAVA_STAGE_DIR},
+    capi::{CApi, CType},
+    reflect::ReflectedTypes,
+    events::Events,
+    wrappers::Wrappers,
+    ios::IosConfig,
+    logging::ModuleLog,
+    settings::DeveloperSettings,
+    templates::TemplateOverrides,
+    config::{ConfigFile, ConfigFiles, IniSection},
+    macho,
+    platform::{UnrealPlatform, UnrealTarget, DEFAULT_TARGETS},
+    profiles::profile_dir,
+    CargoProfiles, Error, PreBuildSteps, Result,
+};
 use std::{
     fs::File,
     io::Write,
@@ -35,10 +51,6 @@ pub enum ModuleCppSources<'a> {
     WithoutDefaultModule(Vec<(&'a str, Vec<CppItem>)>)
 }
 
-pub struct AndroidConfig<'a> {
-    pub permissions: &'a [&'a str],
-}
-
 pub struct ModuleCppImpl<'a> {
     pub pub_includes: &'a[&'a str],
     pub priv_includes: &'a[&'a str],
@@ -86,11 +98,438 @@ impl std::fmt::Display for LoadingPhase {
     }
 }
 
+/// `ModuleRules.PCHUsageMode`
+#[derive(Debug, Clone, Copy, Default)]
+pub enum PCHUsageMode {
+    Default,
+    NoPCHs,
+    NoSharedPCHs,
+    UseSharedPCHs,
+    #[default]
+    UseExplicitOrSharedPCHs,
+}
+impl std::fmt::Display for PCHUsageMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `CppStandardVersion`
+#[derive(Debug, Clone, Copy)]
+pub enum CppStandardVersion {
+    Default,
+    Cpp14,
+    Cpp17,
+    Cpp20,
+    Latest,
+}
+impl std::fmt::Display for CppStandardVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `WarningLevel`
+#[derive(Debug, Clone, Copy)]
+pub enum WarningLevel {
+    Off,
+    Warning,
+    Error,
+}
+impl std::fmt::Display for WarningLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `IWYUSupport`
+#[derive(Debug, Clone, Copy)]
+pub enum IWYUSupport {
+    None,
+    KeepAsIs,
+    KeepPublicAsIsForNow,
+    Full,
+}
+impl std::fmt::Display for IWYUSupport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `CodeOptimization`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeOptimization {
+    Never,
+    InNonDebugBuilds,
+    InShippingBuildsOnly,
+    Always,
+    Default,
+}
+impl std::fmt::Display for CodeOptimization {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `ModuleRules.ModuleType`
+///
+/// `External` modules only wrap third-party libraries: they get a build.cs
+/// but no C++ sources and are not listed in the .uplugin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModuleType {
+    #[default]
+    CPlusPlus,
+    External,
+}
+impl std::fmt::Display for ModuleType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// Where in the generated `ModuleRules` constructor a [`CsSnippet`] goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsHook {
+    BeforeDeps,
+    AfterDeps,
+    /// Inside the `if (Target.Platform == ...)` block of the platform, which
+    /// is created if the module has no libraries for it.
+    Platform(UnrealPlatform),
+    ConstructorEnd,
+}
+
+/// Raw C# the generator doesn't model, e.g. `bPrecompile = true;`.
+pub struct CsSnippet<'a> {
+    /// Emitted as a comment above the code.
+    pub name: &'a str,
+    pub hook: CsHook,
+    pub code: &'a str,
+}
+
+/// Strips the common leading whitespace of `code` and indents it by `depth` spaces.
+fn indent(code: &str, depth: usize) -> String {
+    let lines = code.trim_matches('\n').lines().map(str::trim_end).collect::<Vec<_>>();
+    let common = lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|l| if l.is_empty() { String::new() } else { format!("{:depth$}{}", "", &l[common..]) })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Defined for modules built with `CodeOptimization.Never`, as `Module.debug`
+/// used to.
+const DEBUG_BUILD: &str = "DEBUG_BUILD=1";
+
+/// `ModuleRules` properties. `None` leaves the engine default in place.
+#[derive(Default)]
+pub struct BuildOptions<'a> {
+    pub ty: ModuleType,
+    pub pch_usage: PCHUsageMode,
+    pub private_pch_header_file: Option<&'a str>,
+    pub enable_exceptions: Option<bool>,
+    pub use_rtti: Option<bool>,
+    pub cpp_standard: Option<CppStandardVersion>,
+    pub use_unity: Option<bool>,
+    pub shadow_variable_warning_level: Option<WarningLevel>,
+    pub warnings_as_errors: Option<bool>,
+    pub iwyu_support: Option<IWYUSupport>,
+    pub legacy_public_include_paths: Option<bool>,
+    pub optimize_code: Option<CodeOptimization>,
+    /// Overrides `optimize_code` for specific configurations, e.g.
+    /// `&[(TargetConfiguration::Development, CodeOptimization::Never)]` to
+    /// debug the module in an otherwise optimized editor.
+    pub optimize_code_per_configuration: &'a [(TargetConfiguration, CodeOptimization)],
+    /// Extra `using` directives, e.g. `System.Collections.Generic`.
+    pub usings: &'a [&'a str],
+    pub snippets: &'a [CsSnippet<'a>],
+    /// Members added to the `ModuleRules` class after the constructor.
+    pub methods: &'a [&'a str],
+}
+impl<'a> BuildOptions<'a> {
+    fn snippets(&self, hook: CsHook, depth: usize) -> Vec<String> {
+        self.snippets
+            .iter()
+            .filter(|s| s.hook == hook)
+            .map(|s| format!("{:depth$}// {}\n{}", "", s.name, indent(s.code, depth)))
+            .collect()
+    }
+
+    fn assignments(&self) -> Vec<String> {
+        if self.ty == ModuleType::External {
+            return vec![format!("Type = ModuleType.{}", self.ty)];
+        }
+        let mut out = vec![format!("PCHUsage = ModuleRules.PCHUsageMode.{}", self.pch_usage)];
+        if let Some(v) = self.private_pch_header_file {
+            out.push(format!("PrivatePCHHeaderFile = \"{}\"", v));
+        }
+        if let Some(v) = self.enable_exceptions {
+            out.push(format!("bEnableExceptions = {}", v));
+        }
+        if let Some(v) = self.use_rtti {
+            out.push(format!("bUseRTTI = {}", v));
+        }
+        if let Some(v) = self.cpp_standard {
+            out.push(format!("CppStandard = CppStandardVersion.{}", v));
+        }
+        if let Some(v) = self.use_unity {
+            out.push(format!("bUseUnity = {}", v));
+        }
+        if let Some(v) = self.shadow_variable_warning_level {
+            out.push(format!("ShadowVariableWarningLevel = WarningLevel.{}", v));
+        }
+        if let Some(v) = self.warnings_as_errors {
+            out.push(format!("bWarningsAsErrors = {}", v));
+        }
+        if let Some(v) = self.iwyu_support {
+            out.push(format!("IWYUSupport = IWYUSupport.{}", v));
+        }
+        if let Some(v) = self.legacy_public_include_paths {
+            out.push(format!("bLegacyPublicIncludePaths = {}", v));
+        }
+        if let Some(v) = self.optimize_code {
+            out.push(format!("OptimizeCode = CodeOptimization.{}", v));
+        }
+        out
+    }
+
+    /// `optimize_code_per_configuration` as `(condition, statements)` pairs.
+    fn optimization_blocks(&self) -> Vec<(String, Vec<String>)> {
+        let mut groups: Vec<(CodeOptimization, Vec<Cond>)> = vec![];
+        for (configuration, optimization) in self.optimize_code_per_configuration {
+            let cond = Cond::Configuration(*configuration);
+            match groups.iter_mut().find(|(o, _)| o == optimization) {
+                Some((_, conds)) => conds.push(cond),
+                None => groups.push((*optimization, vec![cond])),
+            }
+        }
+        groups
+            .iter()
+            .map(|(o, conds)| {
+                let mut statements = vec![format!("OptimizeCode = CodeOptimization.{}", o)];
+                if *o == CodeOptimization::Never {
+                    statements.push(format!("PublicDefinitions.Add(\"{}\")", DEBUG_BUILD));
+                }
+                (Cond::Or(conds).to_string(), statements)
+            })
+            .collect()
+    }
+}
+
+/// A preprocessor definition for `PublicDefinitions`/`PrivateDefinitions`.
+#[derive(Debug, Clone, Copy)]
+pub enum Define<'a> {
+    /// `NAME`
+    Flag(&'a str),
+    /// `NAME=42`
+    Int(&'a str, i64),
+    /// `NAME=1` or `NAME=0`
+    Bool(&'a str, bool),
+    /// `NAME="value"`, escaped as a C string literal
+    Str(&'a str, &'a str),
+    /// `NAME=value`, with `value` pasted as is
+    Raw(&'a str, &'a str),
+}
+impl<'a> Define<'a> {
+    pub fn name(&self) -> &'a str {
+        match *self {
+            Self::Flag(n) | Self::Int(n, _) | Self::Bool(n, _) | Self::Str(n, _) | Self::Raw(n, _) => n,
+        }
+    }
+
+    /// The definition as UBT expects it, e.g. `NAME="value"`.
+    pub fn to_definition(&self) -> Result<String> {
+        let name = self.name();
+        let mut chars = name.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(Error::InvalidDefine(name.to_string()));
+        }
+        Ok(match self {
+            Self::Flag(n) => n.to_string(),
+            Self::Int(n, v) => format!("{n}={v}"),
+            Self::Bool(n, v) => format!("{n}={}", *v as u8),
+            Self::Str(n, v) => format!("{n}=\"{}\"", c_escape(v)),
+            Self::Raw(n, v) => format!("{n}={v}"),
+        })
+    }
+
+    /// [`to_definition`](Self::to_definition) as the contents of a C# string literal.
+    fn to_cs(self) -> Result<String> {
+        Ok(cs_escape(&self.to_definition()?))
+    }
+}
+
+/// Escapes `s` for a C/C++ string literal.
+pub(crate) fn c_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            // octal escapes, unlike \x, can't swallow the characters that follow
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u8)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn cs_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
 pub enum Dep<'a> {
     Str(&'a str),
     Ed(&'a str),
 }
 
+/// `TargetType`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetType {
+    Game,
+    Editor,
+    Client,
+    Server,
+    Program,
+}
+impl std::fmt::Display for TargetType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// `UnrealTargetConfiguration`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetConfiguration {
+    Debug,
+    DebugGame,
+    Development,
+    Shipping,
+    Test,
+}
+impl std::fmt::Display for TargetConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{:?}", self))
+    }
+}
+
+/// A condition on the `ReadOnlyTargetRules` a module is built for.
+#[derive(Debug, Clone, Copy)]
+pub enum Cond<'a> {
+    Platform(UnrealPlatform),
+    TargetType(TargetType),
+    Configuration(TargetConfiguration),
+    /// `Target.bBuildEditor`
+    Editor,
+    Not(&'a Cond<'a>),
+    And(&'a [Cond<'a>]),
+    Or(&'a [Cond<'a>]),
+}
+impl<'a> std::fmt::Display for Cond<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn operand(f: &mut std::fmt::Formatter<'_>, cond: &Cond) -> std::fmt::Result {
+            match cond {
+                Cond::And(c) | Cond::Or(c) if c.len() > 1 => write!(f, "({})", cond),
+                _ => write!(f, "{}", cond),
+            }
+        }
+        fn join(f: &mut std::fmt::Formatter<'_>, conds: &[Cond], op: &str, empty: &str) -> std::fmt::Result {
+            if conds.is_empty() {
+                return f.write_str(empty);
+            }
+            for (i, cond) in conds.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(op)?;
+                }
+                operand(f, cond)?;
+            }
+            Ok(())
+        }
+        match self {
+            Self::Platform(p) => write!(f, "Target.Platform == UnrealTargetPlatform.{}", p),
+            Self::TargetType(t) => write!(f, "Target.Type == TargetType.{}", t),
+            Self::Configuration(c) => write!(f, "Target.Configuration == UnrealTargetConfiguration.{}", c),
+            Self::Editor => f.write_str("Target.bBuildEditor"),
+            Self::Not(c @ (Self::Editor | Self::Not(_))) => write!(f, "!{}", c),
+            Self::Not(c) => write!(f, "!({})", c),
+            Self::And(c) => join(f, c, " && ", "true"),
+            Self::Or(c) => join(f, c, " || ", "false"),
+        }
+    }
+}
+
+/// Dependencies, include paths and definitions only added when `cond` holds.
+pub struct Conditional<'a> {
+    pub cond: Cond<'a>,
+    pub pub_dep_mods: &'a [&'a str],
+    pub priv_dep_mods: &'a [&'a str],
+    pub pub_include_paths: &'a [&'a str],
+    pub priv_include_paths: &'a [&'a str],
+    pub pub_defs: &'a [Define<'a>],
+    pub priv_defs: &'a [Define<'a>],
+}
+impl<'a> Conditional<'a> {
+    pub fn new(cond: Cond<'a>) -> Self {
+        Self {
+            cond,
+            pub_dep_mods: &[],
+            priv_dep_mods: &[],
+            pub_include_paths: &[],
+            priv_include_paths: &[],
+            pub_defs: &[],
+            priv_defs: &[],
+        }
+    }
+
+    fn statements(&self) -> Result<Vec<String>> {
+        let quoted = |items: &[&str]| items
+            .iter()
+            .map(|p| format!("\"{}\"", p))
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut out = vec![];
+        for (list, items) in [
+            ("PublicDependencyModuleNames", self.pub_dep_mods),
+            ("PrivateDependencyModuleNames", self.priv_dep_mods),
+            ("PublicIncludePaths", self.pub_include_paths),
+            ("PrivateIncludePaths", self.priv_include_paths),
+        ] {
+            if !items.is_empty() {
+                out.push(format!("{}.AddRange(new string[] {{ {} }})", list, quoted(items)));
+            }
+        }
+        for (list, defs) in [("PublicDefinitions", self.pub_defs), ("PrivateDefinitions", self.priv_defs)] {
+            for def in defs {
+                out.push(format!("{}.Add(\"{}\")", list, def.to_cs()?));
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[derive(Serialize)]
 pub struct ModuleProxy<'a> {
     pub name: &'a str,
     pub ty: HostType,
@@ -111,75 +550,517 @@ impl<'a> From<&Module<'a>> for ModuleProxy<'a> {
 pub struct Module<'a> {
     pub name: &'a str,
     pub android: Option<AndroidConfig<'a>>,
+    pub ios: Option<IosConfig<'a>>,
+    pub rust_api: Option<RustApi<'a>>,
+    /// Generate `U<Module>Subsystem` as the gameplay-facing entry point.
+    pub subsystem: Option<Subsystem<'a>>,
+    /// Generate `U<Module>Settings` shown in Project Settings.
+    ///
+    /// All settings share the class's section of the plugin's own
+    /// `Config/Default<Plugin>.ini` (`config = <Plugin>`), which ships with
+    /// the plugin and is layered under the project's config, so there is no
+    /// per-setting section or ini file.
+    pub settings: Option<DeveloperSettings<'a>>,
+    /// Generate `<Module>Log.h/.cpp` with the module's log category, stats
+    /// group and console variables.
+    pub log: Option<ModuleLog<'a>>,
     pub pub_dep_mods: &'a [&'a str],
     pub priv_dep_mods: &'a [Dep<'a>],
     pub pub_include_paths: &'a [&'a str],
     pub priv_include_paths: &'a [&'a str],
-    pub priv_defs: &'a [(&'a str, &'a str)],
-    pub pub_defs: &'a [(&'a str, &'a str)],
+    pub priv_defs: &'a [Define<'a>],
+    pub pub_defs: &'a [Define<'a>],
+    pub conditionals: &'a [Conditional<'a>],
     pub whitelist_platforms: &'a [&'a str],
+    /// Rust libraries linked into the module, staged for every target.
+    ///
+    /// On Win64, Mac, Linux and LinuxArm64 the default module loads them
+    /// from the staging folder in `StartupModule`. A desktop platform outside
+    /// the configured targets only logs a warning at startup. Android loads them through the APL's
+    /// `soLoadLibrary` and iOS/tvOS at launch. Modules without a default
+    /// module must load them themselves.
     pub external_dylibs: &'a[&'a str],
     pub ty: HostType,
     pub loading_phase: LoadingPhase,
     pub sources: ModuleCppSources<'a>,
-    pub debug: bool,
+    pub build_options: BuildOptions<'a>,
+}
+
+/// C++ generated from the `extern "C"` API of the crate the module links.
+#[derive(Serialize)]
+pub struct RustApi<'a> {
+    /// The crate's `src` directory, or a single `.rs` file.
+    pub src: &'a Path,
+    /// Also generate `<Module>Rust.h/.cpp` with `FString`/`TArray` and RAII
+    /// handle wrappers over the raw declarations.
+    pub wrappers: bool,
+    /// Functions exposed through `U<Module>BlueprintLibrary`.
+    pub blueprint_functions: &'a [BlueprintFunction<'a>],
+    /// `#[repr(C)]` structs and enums mirrored as `F<Module><Name>` USTRUCTs
+    /// and `E<Module><Name>` UENUMs in `<Module>Types.h`.
+    pub reflected_types: &'a [&'a str],
+    /// Rust callbacks broadcast as dynamic multicast delegates of
+    /// `U<Module>Events`, an engine subsystem.
+    pub events: &'a [RustEvent<'a>],
+}
+impl<'a> RustApi<'a> {
+    pub fn new(src: &'a Path) -> Self {
+        Self { src, wrappers: false, blueprint_functions: &[], reflected_types: &[], events: &[] }
+    }
+
+    /// `Public/<Module>RustApi.h` and the wrappers, by file name.
+    fn sources(&self, api: &CApi, module_name: &str, templates: TemplateOverrides) -> Result<Vec<(String, Vec<CppItem>)>> {
+        /// Context of the overrides of the files generated from the API.
+        #[derive(Serialize)]
+        struct Context<'a> { name: &'a str, api: &'a CApi, rust_api: &'a RustApi<'a> }
+
+        let context = Context { name: module_name, api, rust_api: self };
+        let mut out = vec![(format!("{}RustApi", module_name), vec![CppItem::Header(CppHeader {
+            is_pub: true,
+            contents: templates.render_with("RustApi.h.jinja", api.header(module_name), &context)?,
+        })])];
+        let mut files = |name: &str, header: String, source: String| -> Result<()> {
+            out.push((format!("{}{}", module_name, name), vec![
+                CppItem::Header(CppHeader { is_pub: true, contents: templates.render_with(&format!("{}.h.jinja", name), header, &context)? }),
+                CppItem::Source(CppSource { contents: templates.render_with(&format!("{}.cpp.jinja", name), source, &context)? }),
+            ]));
+            Ok(())
+        };
+        if self.wrappers {
+            let wrappers = Wrappers::new(api, module_name);
+            files("Rust", wrappers.header(module_name)?, wrappers.source(module_name))?;
+        }
+        if !self.reflected_types.is_empty() {
+            let types = ReflectedTypes::new(api, self.reflected_types, module_name)?;
+            files("Types", types.header()?, types.source()?)?;
+        }
+        if !self.blueprint_functions.is_empty() {
+            let (header, source) = Wrappers::new(api, module_name).blueprint_library(self.blueprint_functions, module_name)?;
+            files("BlueprintLibrary", header, source)?;
+        }
+        if !self.events.is_empty() {
+            let events = Events::new(api, self.events, module_name)?;
+            files("Events", events.header()?, events.source()?)?;
+        }
+        Ok(out)
+    }
+}
+
+/// An exported Rust function made callable from Blueprint.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlueprintFunction<'a> {
+    /// The Rust function's name.
+    pub name: &'a str,
+    pub category: &'a str,
+    /// `BlueprintPure` instead of `BlueprintCallable`.
+    pub pure: bool,
+}
+
+/// A Rust callback registration broadcast as a Blueprint event.
+///
+/// `register` takes an `Option<extern "C" fn(...)>` and optionally a
+/// `*mut c_void` passed back as the callback's user data parameter; it is
+/// called with the callback when the subsystem initializes and with null
+/// when it deinitializes. The callback may be invoked from any thread, its
+/// arguments are copied and the delegate broadcast on the game thread.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct RustEvent<'a> {
+    /// The delegate property, e.g. `OnProgress`.
+    pub name: &'a str,
+    /// The Rust function registering the callback.
+    pub register: &'a str,
+}
+
+/// The engine class a generated subsystem derives from.
+#[derive(Debug, Clone, Copy)]
+pub enum SubsystemKind {
+    GameInstance,
+    World,
+    Engine,
+    /// Only for editor modules.
+    Editor,
+}
+impl SubsystemKind {
+    fn base_class(self) -> &'static str {
+        match self {
+            SubsystemKind::GameInstance => "UGameInstanceSubsystem",
+            SubsystemKind::World => "UWorldSubsystem",
+            SubsystemKind::Engine => "UEngineSubsystem",
+            SubsystemKind::Editor => "UEditorSubsystem",
+        }
+    }
+
+    fn include(self) -> &'static str {
+        match self {
+            SubsystemKind::GameInstance => "Subsystems/GameInstanceSubsystem.h",
+            SubsystemKind::World => "Subsystems/WorldSubsystem.h",
+            SubsystemKind::Engine => "Subsystems/EngineSubsystem.h",
+            SubsystemKind::Editor => "EditorSubsystem.h",
+        }
+    }
+}
+
+/// A `U<Module>Subsystem` skeleton calling into the Rust library.
+#[derive(Debug, Clone, Copy)]
+pub struct Subsystem<'a> {
+    pub kind: SubsystemKind,
+    /// `extern "C" fn()` called at the end of `Initialize`.
+    pub init: Option<&'a str>,
+    /// `extern "C" fn()` called at the start of `Deinitialize`.
+    pub shutdown: Option<&'a str>,
+}
+impl<'a> Subsystem<'a> {
+    /// `<Module>Subsystem.h/.cpp`. The Rust functions are declared unless
+    /// the module's `<Module>RustApi.h` already does, in which case they must
+    /// be part of `api`.
+    fn sources(&self, module_name: &str, api: Option<&CApi>, templates: TemplateOverrides) -> Result<(String, Vec<CppItem>)> {
+        #[derive(Template, Serialize)]
+        #[template(path = "Subsystem.h.jinja", escape = "none")]
+        struct Header<'a> { name: &'a str, base_class: &'a str, include: &'a str }
+        #[derive(Template, Serialize)]
+        #[template(path = "Subsystem.cpp.jinja", escape = "none")]
+        struct Source<'a> { name: &'a str, declare: Vec<&'a str>, init: Option<&'a str>, shutdown: Option<&'a str> }
+
+        let header = Header { name: module_name, base_class: self.kind.base_class(), include: self.kind.include() };
+        let functions = self.init.into_iter().chain(self.shutdown);
+        let declare = match api {
+            Some(api) => {
+                for name in functions {
+                    let f = api.functions.iter().find(|f| f.name == name).ok_or_else(|| Error::UnknownFunction(name.to_string()))?;
+                    if !f.params.is_empty() || f.ret != CType::Void {
+                        return Err(Error::UnsupportedType(format!("{}: subsystem functions take and return nothing", name)));
+                    }
+                }
+                vec![]
+            }
+            None => functions.collect(),
+        };
+        let source = Source { name: module_name, declare, init: self.init, shutdown: self.shutdown };
+        Ok((format!("{}Subsystem", module_name), vec![
+            CppItem::Header(CppHeader { is_pub: true, contents: templates.render("Subsystem.h.jinja", &header)? }),
+            CppItem::Source(CppSource { contents: templates.render("Subsystem.cpp.jinja", &source)? }),
+        ]))
+    }
+}
+
+/// Code spliced into the generated default module's .cpp.
+#[derive(Default, Serialize)]
+struct ModuleHooks {
+    /// File scope code after the includes.
+    preamble: Vec<String>,
+    /// Statements run in `StartupModule`, in order.
+    startup: Vec<String>,
+    /// Statements run in `ShutdownModule`, in order.
+    shutdown: Vec<String>,
+}
+
+/// A target the plugin is built for, with the cargo output directory its
+/// libraries are staged from, if any, and the profile they were built with
+/// when staging library variants.
+#[derive(Clone, Copy)]
+pub struct StagedTarget<'a> {
+    pub target: UnrealTarget,
+    pub lib_dir: Option<&'a Path>,
+    pub profile: Option<&'a str>,
+}
+impl<'a> StagedTarget<'a> {
+    fn stage_dir(&self) -> std::path::PathBuf {
+        let dir = self.target.stage_dir();
+        match self.profile {
+            Some(profile) => dir.join(profile_dir(profile)),
+            None => dir,
+        }
+    }
 }
 
 impl<'a> Module<'a> {
-    pub fn generate(self, plugin_dir: &Path, is_singular_module: bool) -> Result<()> {
-        let mut moduledir = plugin_dir.join("Source");
-        if !is_singular_module {
-            moduledir = moduledir.join(self.name);
+    /// Modules the generated C++ needs besides `Core`.
+    fn generated_code_deps(&self) -> Vec<&'static str> {
+        let mut deps = vec![];
+        if let Some(api) = self.rust_api.as_ref() {
+            let uses_engine = !api.blueprint_functions.is_empty() || !api.events.is_empty();
+            if !api.reflected_types.is_empty() || uses_engine {
+                deps.push("CoreUObject");
+            }
+            if uses_engine {
+                deps.push("Engine");
+            }
+        }
+        if let Some(subsystem) = self.subsystem.as_ref() {
+            deps.extend(["CoreUObject", "Engine"]);
+            if let SubsystemKind::Editor = subsystem.kind {
+                deps.push("EditorSubsystem");
+            }
         }
+        if self.settings.is_some() {
+            deps.extend(["CoreUObject", "DeveloperSettings"]);
+        }
+        deps
+    }
+
+    /// Directory of the module relative to the plugin, with `/` separators.
+    fn relative_dir(&self, is_singular_module: bool) -> String {
+        if is_singular_module {
+            "Source".to_string()
+        } else {
+            format!("Source/{}", self.name)
+        }
+    }
+
+    /// Files besides C++ sources the module needs packaged, as
+    /// `FilterPlugin.ini` paths: every library build.cs and the UPL files
+    /// reference, whether staged here or by the pre-build steps.
+    fn packaged_files(&self, is_singular_module: bool, targets: &[StagedTarget], variants: Option<&CargoProfiles>) -> Vec<String> {
+        let dir = format!("/{}", self.relative_dir(is_singular_module));
+        let mut stage_dirs: Vec<(std::path::PathBuf, &UnrealTarget)> = vec![];
+        for staged in targets {
+            let mut dirs = match variants {
+                Some(profiles) => profiles.dirs().iter().map(|(d, _)| staged.target.stage_dir().join(d)).collect(),
+                None => vec![staged.target.stage_dir()],
+            };
+            if staged.lib_dir.is_some() {
+                dirs.push(staged.stage_dir());
+            }
+            for d in dirs {
+                if !stage_dirs.iter().any(|(s, t)| *s == d && **t == staged.target) {
+                    stage_dirs.push((d, &staged.target));
+                }
+            }
+        }
+        let mut out = vec![];
+        for (stage_dir, target) in stage_dirs {
+            let stage_dir = stage_dir
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+                .join("/");
+            for dylib in self.external_dylibs {
+                let files = std::iter::once(target.dylib_filename(dylib))
+                    .chain(target.import_lib_filename(dylib));
+                for filename in files {
+                    let path = format!("{}/{}/{}", dir, stage_dir, filename);
+                    if !out.contains(&path) {
+                        out.push(path);
+                    }
+                }
+            }
+        }
+        if let Some(android) = self.android.as_ref() {
+            out.push(format!("{}/BaseAPL.xml", dir));
+            if !android.java_sources.is_empty() {
+                out.push(format!("{}/{}/...", dir, JAVA_STAGE_DIR));
+            }
+        }
+        if let Some(ios) = self.ios.as_ref() {
+            out.push(format!("{}/IOS_UPL.xml", dir));
+            if !ios.entitlements.is_empty() {
+                out.push(format!("{}/IOS/{}.entitlements", dir, self.name));
+            }
+        }
+        out
+    }
+
+    /// `variants` is set when libraries are staged per cargo profile.
+    pub fn generate(
+        self,
+        plugin_name: &str,
+        plugin_dir: &Path,
+        is_singular_module: bool,
+        targets: &[StagedTarget],
+        variants: Option<&CargoProfiles>,
+        templates: TemplateOverrides,
+    ) -> Result<()> {
+        let relative_dir = self.relative_dir(is_singular_module);
+        let moduledir = plugin_dir.join(&relative_dir);
         std::fs::create_dir_all(&moduledir).unwrap();
         
         write_only_if_changed(
             &moduledir.join(format!("{}.build.cs", self.name)),
-            || {
-                Builder::write_build(
-                    self.external_dylibs,
-                    &self.name,
-                    self.pub_dep_mods,
-                    self.priv_dep_mods,
-                    self.pub_include_paths,
-                    self.priv_include_paths,
-                    self.pub_defs,
-                    self.priv_defs,
-                    self.debug
-                )
-            },
+            || Builder::write_build(&self, targets, variants, templates),
         )?;
 
+        // targets sharing a folder (Mac arm64 + x64) are merged into one library
+        let mut stage_groups: Vec<(std::path::PathBuf, Vec<&StagedTarget>)> = vec![];
+        for staged in targets.iter().filter(|t| t.lib_dir.is_some()) {
+            let dir = staged.stage_dir();
+            match stage_groups.iter_mut().find(|(d, _)| *d == dir) {
+                Some((_, group)) => group.push(staged),
+                None => stage_groups.push((dir, vec![staged])),
+            }
+        }
+        for (dir, group) in stage_groups {
+            let stage_dir = moduledir.join(&dir);
+            std::fs::create_dir_all(&stage_dir)?;
+            for dylib in self.external_dylibs {
+                match group.as_slice() {
+                    [staged] => {
+                        let files = std::iter::once(staged.target.dylib_filename(dylib))
+                            .chain(staged.target.import_lib_filename(dylib));
+                        for filename in files {
+                            std::fs::copy(staged.lib_dir.unwrap().join(&filename), stage_dir.join(&filename))?;
+                        }
+                    }
+                    [first, ..] if first.target.platform.is_apple() => {
+                        let filename = first.target.dylib_filename(dylib);
+                        let images = group
+                            .iter()
+                            .map(|s| std::fs::read(s.lib_dir.unwrap().join(&filename)))
+                            .collect::<std::io::Result<Vec<_>>>()?;
+                        let images = images.iter().map(Vec::as_slice).collect::<Vec<_>>();
+                        std::fs::write(stage_dir.join(&filename), macho::create_universal(&images)?)?;
+                    }
+                    _ => return Err(Error::StageConflict(dir)),
+                }
+            }
+        }
+
         let source_code = self.sources;//.map(|f| f(self.name, &module.name, lib_name.as_str())).transpose()?;
 
-        if !self.external_dylibs.is_empty() {
-            if let Some(android) = self.android.as_ref() {
-                let base_apl_file = File::create(moduledir.join("BaseAPL.xml")).unwrap();
-                Builder::write_base_apl(android.permissions, self.external_dylibs, base_apl_file).unwrap();
+        if let Some(android) = self.android.as_ref() {
+            let base_apl = Builder::write_base_apl(android, self.external_dylibs, variants, templates)?;
+            std::fs::write(moduledir.join("BaseAPL.xml"), base_apl)?;
+        }
+        if let Some(ios) = self.ios.as_ref() {
+            std::fs::write(moduledir.join("IOS_UPL.xml"), Builder::write_ios_upl(ios, templates)?)?;
+            if !ios.entitlements.is_empty() {
+                std::fs::create_dir_all(moduledir.join("IOS"))?;
+                let entitlements = Builder::write_entitlements(ios, templates)?;
+                std::fs::write(moduledir.join("IOS").join(format!("{}.entitlements", self.name)), entitlements)?;
             }
         }
 
         std::fs::create_dir_all(moduledir.join("Private")).unwrap();
         std::fs::create_dir_all(moduledir.join("Public")).unwrap();
 
-        fn get_default_module<'a>(module_filename: &'a str, module_name: &'a str) -> Result<(&'a str, Vec<CppItem>)> {
+        let mut hooks = ModuleHooks::default();
+        if !self.external_dylibs.is_empty() {
+            // the module's own category, or a file local one named like it
+            let (log_decl, log_category) = match self.log.as_ref() {
+                Some(log) => (format!("#include \"{}Log.h\"", self.name), log.category.to_string()),
+                None => (format!("DEFINE_LOG_CATEGORY_STATIC(Log{}, Log, All);", self.name), format!("Log{}", self.name)),
+            };
+            // RUST_LIB_DIR is only defined for platforms loading the libraries at runtime
+            let mut preamble = vec![
+                "#ifdef RUST_LIB_DIR".to_string(),
+                "#include \"HAL/PlatformProcess.h\"".to_string(),
+                "#include \"Interfaces/IPluginManager.h\"".to_string(),
+                "#include \"Misc/Paths.h\"".to_string(),
+                String::new(),
+                "static TArray<void*> LibHandles;".to_string(),
+            ];
+            // a configured desktop target must have its libraries staged
+            let mut desktop = targets
+                .iter()
+                .map(|t| t.target.platform)
+                .filter(|p| p.is_desktop())
+                .map(UnrealPlatform::cpp_condition)
+                .collect::<Vec<_>>();
+            desktop.sort();
+            desktop.dedup();
+            if !desktop.is_empty() {
+                preamble.push(format!("#elif {}", desktop.join(" || ")));
+                preamble.push(format!("#error \"{}: no Rust libraries are staged for this platform\"", c_escape(self.name)));
+            }
+            preamble.push("#endif".to_string());
+            preamble.push("#if defined(RUST_LIB_DIR) || PLATFORM_DESKTOP".to_string());
+            preamble.push(log_decl);
+            preamble.push("#endif".to_string());
+            hooks.preamble.push(preamble.join("\n"));
+            let names = self.external_dylibs
+                .iter()
+                .map(|d| format!("TEXT(\"{}\")", d))
+                .collect::<Vec<_>>()
+                .join(", ");
+            hooks.startup.push([
+                "#ifdef RUST_LIB_DIR".to_string(),
+                format!("    if (const TSharedPtr<IPlugin> Plugin = IPluginManager::Get().FindPlugin(TEXT(\"{}\"))) {{", c_escape(plugin_name)),
+                format!("        const FString LibDir = FPaths::Combine(Plugin->GetBaseDir(), TEXT(\"{}\"), TEXT(RUST_LIB_DIR));", relative_dir),
+                format!("        for (const TCHAR* Name : {{ {} }}) {{", names),
+                "            const FString Path = FPaths::Combine(LibDir, FString::Printf(TEXT(\"%s%s.%s\"), FPlatformProcess::GetModulePrefix(), Name, FPlatformProcess::GetModuleExtension()));".to_string(),
+                "            if (void* Handle = FPlatformProcess::GetDllHandle(*Path)) {".to_string(),
+                "                LibHandles.Add(Handle);".to_string(),
+                "            } else {".to_string(),
+                format!("                UE_LOG({}, Error, TEXT(\"failed to load %s\"), *Path);", log_category),
+                "            }".to_string(),
+                "        }".to_string(),
+                "    }".to_string(),
+                // a desktop platform outside the targets, e.g. a Linux editor build
+                "#elif PLATFORM_DESKTOP".to_string(),
+                format!("    UE_LOG({}, Warning, TEXT(\"no Rust libraries are staged for this platform\"));", log_category),
+                "#endif".to_string(),
+            ].join("\n"));
+            hooks.shutdown.push([
+                "#ifdef RUST_LIB_DIR",
+                "    for (int32 i = LibHandles.Num() - 1; i >= 0; --i) {",
+                "        FPlatformProcess::FreeDllHandle(LibHandles[i]);",
+                "    }",
+                "    LibHandles.Empty();",
+                "#endif",
+            ].join("\n"));
+        }
+        if let Some(rust_log) = self.log.as_ref().and_then(|log| log.rust_log) {
+            hooks.preamble.push([
+                format!("#include \"{}Log.h\"", self.name),
+                String::new(),
+                format!("extern \"C\" void {}(void (*Callback)(uint8, const ANSICHAR*, SIZE_T, const ANSICHAR*, SIZE_T), uint8 MaxLevel);", rust_log),
+            ].join("\n"));
+            hooks.startup.push(format!("    {}(&{}, {}());", rust_log, ModuleLog::callback(self.name), ModuleLog::level(self.name)));
+            // before the libraries are unloaded
+            hooks.shutdown.insert(0, format!("    {}(nullptr, 0);", rust_log));
+        }
+        if let Some(rust_settings) = self.settings.as_ref().and_then(|settings| settings.rust_settings) {
+            hooks.preamble.push([
+                format!("#include \"{}Settings.h\"", self.name),
+                String::new(),
+                format!("extern \"C\" void {}(const {}* Settings);", rust_settings, DeveloperSettings::accessors(self.name)),
+            ].join("\n"));
+            hooks.startup.push(format!("    {}({}());", rust_settings, DeveloperSettings::accessors_getter(self.name)));
+            hooks.shutdown.insert(0, format!("    {}(nullptr);", rust_settings));
+        }
+        if let Some(android) = self.android.as_ref() {
+            for source in android.java_sources {
+                let contents = std::fs::read_to_string(source)?;
+                let dst = moduledir.join(JAVA_STAGE_DIR).join(java_source_path(source, &contents));
+                std::fs::create_dir_all(dst.parent().unwrap())?;
+                std::fs::write(dst, contents)?;
+            }
+            if let Some(bootstrap) = android.jni_bootstrap {
+                hooks.preamble.push([
+                    "#if PLATFORM_ANDROID".to_string(),
+                    "#include \"Android/AndroidApplication.h\"".to_string(),
+                    "#include \"Android/AndroidJNI.h\"".to_string(),
+                    String::new(),
+                    format!("extern \"C\" void {bootstrap}(JavaVM* vm, jobject activity);"),
+                    "#endif".to_string(),
+                ].join("\n"));
+                hooks.startup.push([
+                    "#if PLATFORM_ANDROID".to_string(),
+                    "    JavaVM* VM = nullptr;".to_string(),
+                    "    FAndroidApplication::GetJavaEnv()->GetJavaVM(&VM);".to_string(),
+                    format!("    {bootstrap}(VM, FJavaWrapper::GameActivityThis);"),
+                    "#endif".to_string(),
+                ].join("\n"));
+            }
+        }
+
+        fn get_default_module<'a>(module_filename: &'a str, module_name: &'a str, hooks: &ModuleHooks, templates: TemplateOverrides) -> Result<(&'a str, Vec<CppItem>)> {
             Ok((module_filename, {
                 let name = module_name;
                 vec![CppItem::Header(CppHeader {
                     is_pub: true,
                     contents: {
-                        #[derive(Template)]
+                        #[derive(Template, Serialize)]
                         #[template(path = "DefaultModule.h.jinja", escape = "none")]
                         struct Template<'a> { name: &'a str }
-                        Template { name }.render()?
+                        templates.render("DefaultModule.h.jinja", &Template { name })?
                     },
                 }),
                 CppItem::Source(CppSource {
                     contents: {
-                        #[derive(Template)]
+                        #[derive(Template, Serialize)]
                         #[template(path = "DefaultModule.cpp.jinja", escape = "none")]
-                        struct Template<'a> { filename: &'a str, name: &'a str, }
-                        Template { filename: module_filename, name }.render()?
+                        struct Template<'a> { filename: &'a str, name: &'a str, hooks: &'a ModuleHooks }
+                        templates.render("DefaultModule.cpp.jinja", &Template { filename: module_filename, name, hooks })?
                     }
                 })]
             }))
@@ -187,13 +1068,56 @@ impl<'a> Module<'a> {
 
         let default_module_filename = format!("{}Module", self.name);
         let sources = match source_code {
-            ModuleCppSources::None => vec![get_default_module(&default_module_filename, self.name)?],
+            _ if self.build_options.ty == ModuleType::External => vec![],
+            ModuleCppSources::None => vec![get_default_module(&default_module_filename, self.name, &hooks, templates)?],
             ModuleCppSources::WithDefaultModule(mut items) => {
-                items.push(get_default_module(&default_module_filename, self.name)?);
+                items.push(get_default_module(&default_module_filename, self.name, &hooks, templates)?);
                 items
             }
             ModuleCppSources::WithoutDefaultModule(items) => items
         };
+        let api = match self.rust_api.as_ref() {
+            Some(rust_api) if self.build_options.ty != ModuleType::External => Some((rust_api, CApi::parse(rust_api.src)?)),
+            _ => None,
+        };
+        let rust_api_sources = match api.as_ref() {
+            Some((rust_api, api)) => rust_api.sources(api, self.name, templates)?,
+            None => vec![],
+        };
+        let mut sources = sources.into_iter().map(|(name, items)| (name.to_string(), items)).collect::<Vec<_>>();
+        sources.extend(rust_api_sources);
+        if let Some(subsystem) = self.subsystem.as_ref() {
+            if self.build_options.ty != ModuleType::External {
+                sources.push(subsystem.sources(self.name, api.as_ref().map(|(_, api)| api), templates)?);
+            }
+        }
+        if let Some(log) = self.log.as_ref() {
+            if self.build_options.ty != ModuleType::External {
+                #[derive(Serialize)]
+                struct Context<'a> { name: &'a str, log: &'a ModuleLog<'a> }
+
+                let context = Context { name: self.name, log };
+                sources.push((format!("{}Log", self.name), vec![
+                    CppItem::Header(CppHeader { is_pub: true, contents: templates.render_with("Log.h.jinja", log.header(self.name), &context)? }),
+                    CppItem::Source(CppSource { contents: templates.render_with("Log.cpp.jinja", log.source(self.name), &context)? }),
+                ]));
+            }
+        }
+        if let Some(settings) = self.settings.as_ref() {
+            if self.build_options.ty != ModuleType::External {
+                #[derive(Serialize)]
+                struct Context<'a> { name: &'a str, plugin: &'a str, settings: &'a DeveloperSettings<'a> }
+
+                let context = Context { name: self.name, plugin: plugin_name, settings };
+                sources.push((format!("{}Settings", self.name), vec![
+                    CppItem::Header(CppHeader {
+                        is_pub: true,
+                        contents: templates.render_with("Settings.h.jinja", settings.header(plugin_name, self.name), &context)?,
+                    }),
+                    CppItem::Source(CppSource { contents: templates.render_with("Settings.cpp.jinja", settings.source(self.name), &context)? }),
+                ]));
+            }
+        }
 
         for (name, files) in sources {
             for item in files {
@@ -207,7 +1131,7 @@ impl<'a> Module<'a> {
                     .create(true)
                     .truncate(true)
                     .open(moduledir.join(if is_pub { "Public" } else { "Private" }).join(&filename))
-                    .expect(format!("failed to open {}", filename).as_str());
+                    .unwrap_or_else(|_| panic!("failed to open {}", filename));
                 write!(file, "{}", contents).unwrap();
             }
         }
@@ -268,6 +1192,7 @@ pub struct Plugin<'a> {
     support_url: &'a str,
     version: u32,
     version_name: &'a str,
+    templates: TemplateOverrides<'a>,
 }
 impl<'a> Plugin<'a> {
     pub fn new(name: &'a str) -> Self {
@@ -292,6 +1217,7 @@ impl<'a> Plugin<'a> {
             support_url: "",
             version: 1,
             version_name: "",
+            templates: TemplateOverrides::default(),
         }
     }
 
@@ -335,6 +1261,11 @@ impl<'a> Plugin<'a> {
         self.icon = Some(bytes);
         self
     }
+    /// Templates in `dir` replace the built-in ones of the same name.
+    pub fn templates(mut self, dir: &'a Path) -> Self {
+        self.templates = TemplateOverrides { dir: Some(dir) };
+        self
+    }
     pub fn docs_url(mut self, url: &'a str) -> Self {
         self.docs_url = url;
         self
@@ -402,7 +1333,7 @@ impl<'a> Plugin<'a> {
         write_only_if_changed(
             &outdir.join(format!("{}.uplugin", self.name)),
             || {
-                self.write_plugin(&self.modules)
+                self.write_plugin(&self.modules, "")
             },
         )?;
 
@@ -416,7 +1347,7 @@ impl<'a> Plugin<'a> {
         Ok(())
     }
 
-    fn write_plugin(&self, modules: &[ModuleProxy]) -> Result<String> {
+    fn write_plugin(&self, modules: &[ModuleProxy], pre_build_steps: &str) -> Result<String> {
         let plugins = self.plugin_deps.iter().map(|i| {
             let mut props = vec![];
             props.push(("Name", format!("\"{}\"", i.name)));
@@ -454,7 +1385,7 @@ impl<'a> Plugin<'a> {
             )
         });
 
-        #[derive(Template)]
+        #[derive(Template, Serialize)]
         #[template(path = "Default.uplugin.jinja", escape = "none")]
         struct UPluginTemplate<'a> {
             file_version: u32,
@@ -472,10 +1403,11 @@ impl<'a> Plugin<'a> {
             is_beta_version: bool,
             installed: bool,
             enabled_by_default: bool,
+            pre_build_steps: &'a str,
             plugins: &'a str,
             modules: &'a[ModuleProxy<'a>],
         }
-        Ok(UPluginTemplate {
+        self.templates.render("Default.uplugin.jinja", &UPluginTemplate {
             file_version: 3,
             version: self.version,
             version_name: self.version_name,
@@ -491,9 +1423,10 @@ impl<'a> Plugin<'a> {
             is_beta_version: self.is_beta_version,
             installed: self.installed,
             enabled_by_default: self.enabled_by_default,
+            pre_build_steps,
             plugins: &plugins.collect::<Vec<_>>().join(", "),
             modules,
-        }.render().unwrap())
+        })
     }
 
 }
@@ -501,6 +1434,11 @@ impl<'a> Plugin<'a> {
 pub struct Builder<'a> {
     plugin: Plugin<'a>,
     modules: Vec<Module<'a>>,
+    targets: Vec<(&'a str, Option<&'a Path>, Option<&'a str>)>,
+    pre_build_steps: Option<PreBuildSteps<'a>>,
+    profiles: CargoProfiles<'a>,
+    variants: bool,
+    config: Vec<(ConfigFile<'a>, &'a [IniSection<'a>])>,
 }
 
 impl<'a> Builder<'a> {
@@ -508,6 +1446,11 @@ impl<'a> Builder<'a> {
         Self {
             plugin: Plugin::new(name),
             modules:  vec![],
+            targets: vec![],
+            pre_build_steps: None,
+            profiles: CargoProfiles::default(),
+            variants: false,
+            config: vec![],
         }
     }
     pub fn disabled(mut self) -> Self {
@@ -552,40 +1495,74 @@ impl<'a> Builder<'a> {
     // }
 
     fn write_base_apl(
-        android_permission_names: &[&str],
+        android: &AndroidConfig,
         dylibs: &[&str],
-        mut out: impl Write,
-    ) -> Result<()> {
-        #[derive(Template)]
+        variants: Option<&CargoProfiles>,
+        templates: TemplateOverrides,
+    ) -> Result<String> {
+        let lines = |items: &[&str]| items.iter().map(|i| xml_escape(i)).collect::<Vec<_>>();
+        let meta_data = |items: &[(&str, &str)]| items
+            .iter()
+            .map(|(k, v)| format!("<meta-data android:name=\"{}\" android:value=\"{}\"/>", xml_escape(k), xml_escape(v)))
+            .collect::<Vec<_>>();
+
+        #[derive(Template, Serialize)]
         #[template(path = "BaseAPL.xml.jinja", escape = "none")]
         struct XmlTemplate<'a> {
-            permission_names: &'a[&'a str],
-            dylibs: &'a[&'a str]
+            permissions: &'a[String],
+            features: &'a[String],
+            dylibs: &'a[&'a str],
+            variants: &'a[(&'a str, Vec<TargetConfiguration>)],
+            proguard_additions: &'a[String],
+            gradle_dependencies: &'a[String],
+            build_gradle_additions: &'a[String],
+            application_meta_data: &'a[String],
+            activity_meta_data: &'a[String],
+            game_activity_import_additions: &'a[String],
+            game_activity_class_additions: &'a[String],
+            java_dir: &'a str,
         }
-        out.write(XmlTemplate {
-            permission_names: android_permission_names,
-            dylibs
-        }.render().unwrap().as_bytes()).unwrap();
-        Ok(())
+        templates.render("BaseAPL.xml.jinja", &XmlTemplate {
+            permissions: &android.permissions.iter().map(|p| p.to_element()).collect::<Vec<_>>(),
+            features: &android.features.iter().map(|f| f.to_element()).collect::<Vec<_>>(),
+            dylibs,
+            variants: &variants.map(CargoProfiles::dirs).unwrap_or_default(),
+            proguard_additions: &lines(android.proguard_additions),
+            gradle_dependencies: &lines(android.gradle_dependencies),
+            build_gradle_additions: &lines(android.build_gradle_additions),
+            application_meta_data: &meta_data(android.application_meta_data),
+            activity_meta_data: &meta_data(android.activity_meta_data),
+            game_activity_import_additions: &lines(android.game_activity_import_additions),
+            game_activity_class_additions: &lines(android.game_activity_class_additions),
+            java_dir: if android.java_sources.is_empty() { "" } else { JAVA_STAGE_DIR },
+        })
     }
 
-    fn write_build(
-        dylibs: &[&str],
-        module_name: &str,
-        pub_dep_mods: &[&str],
-        priv_dep_mods: &[Dep],
-        pub_include_paths: &[&str],
-        priv_include_paths: &[&str],
-        pub_defs: &[(&str, &str)],
-        priv_defs: &[(&str, &str)],
-        debug: bool,
-    ) -> Result<String> {
-        let pub_deps = pub_dep_mods
+    fn write_ios_upl(ios: &IosConfig, templates: TemplateOverrides) -> Result<String> {
+        #[derive(Template, Serialize)]
+        #[template(path = "IOS_UPL.xml.jinja", escape = "none")]
+        struct XmlTemplate {
+            plist: Vec<String>,
+        }
+        templates.render("IOS_UPL.xml.jinja", &XmlTemplate { plist: ios.plist_lines() })
+    }
+
+    fn write_entitlements(ios: &IosConfig, templates: TemplateOverrides) -> Result<String> {
+        #[derive(Template, Serialize)]
+        #[template(path = "Default.entitlements.jinja", escape = "none")]
+        struct PlistTemplate {
+            entitlements: Vec<String>,
+        }
+        templates.render("Default.entitlements.jinja", &PlistTemplate { entitlements: ios.entitlement_lines() })
+    }
+
+    fn write_build(module: &Module, targets: &[StagedTarget], variants: Option<&CargoProfiles>, templates: TemplateOverrides) -> Result<String> {
+        let pub_deps = module.pub_dep_mods
             .iter()
             .map(|p| format!("\"{}\"", p))
             .collect::<Vec<_>>()
             .join(",");
-        let priv_deps_ed = priv_dep_mods
+        let priv_deps_ed = module.priv_dep_mods
             .iter()
             .filter_map(|p| match p {
                 Dep::Ed(s) => Some(s),
@@ -594,35 +1571,184 @@ impl<'a> Builder<'a> {
             .map(|p| format!("\"{}\"", p))
             .collect::<Vec<_>>()
             .join(",");
-        let priv_deps = priv_dep_mods
+        let mut priv_deps = module.priv_dep_mods
             .iter()
             .filter_map(|p| match p {
-                Dep::Str(s) => Some(s),
+                Dep::Str(s) => Some(*s),
                 _ => None
             })
+            .collect::<Vec<_>>();
+        for dep in module.generated_code_deps() {
+            if !priv_deps.contains(&dep) && !module.pub_dep_mods.contains(&dep) {
+                priv_deps.push(dep);
+            }
+        }
+        let priv_deps = priv_deps
+            .iter()
             .map(|p| format!("\"{}\"", p))
             .collect::<Vec<_>>()
             .join(",");
-        let pub_inc = pub_include_paths
+        let pub_inc = module.pub_include_paths
             .iter()
             .map(|p| format!("\"{}\"", p))
             .collect::<Vec<_>>()
             .join(",");
-        let priv_inc = priv_include_paths
+        let priv_inc = module.priv_include_paths
             .iter()
             .map(|p| format!("\"{}\"", p))
             .collect::<Vec<_>>()
             .join(",");
-        let pub_defs = pub_defs
+        let mut pub_defs = module.pub_defs
             .iter()
-            .map(|(k, v)| format!("{k}={v}"))
-            .collect::<Vec<_>>();
-        let priv_defs = priv_defs
+            .map(|d| d.to_cs())
+            .collect::<Result<Vec<_>>>()?;
+        if module.build_options.optimize_code == Some(CodeOptimization::Never) {
+            pub_defs.push(DEBUG_BUILD.to_string());
+        }
+        let priv_defs = module.priv_defs
             .iter()
-            .map(|(k, v)| format!("{k}={v}"))
-            .collect::<Vec<_>>();
+            .map(|d| d.to_cs())
+            .collect::<Result<Vec<_>>>()?;
 
-        #[derive(Template)]
+        #[derive(Serialize)]
+        struct ConditionalBlock {
+            cond: String,
+            statements: Vec<String>,
+        }
+        let mut conditionals = vec![];
+        for c in module.conditionals {
+            let statements = c.statements()?;
+            if !statements.is_empty() {
+                conditionals.push(ConditionalBlock { cond: c.cond.to_string(), statements });
+            }
+        }
+
+        #[derive(Default, Serialize)]
+        struct PlatformBranch {
+            platform: Option<UnrealPlatform>,
+            /// `(key, file)` UPL files registered with `AdditionalPropertiesForReceipt`.
+            receipts: Vec<(&'static str, &'static str)>,
+            priv_deps: Vec<&'static str>,
+            /// C# expressions added to `PrivateDefinitions`.
+            priv_defs: Vec<String>,
+            /// Variables declared at the top of the branch.
+            locals: Vec<String>,
+            libs: Vec<String>,
+            delay_load: Vec<String>,
+            runtime_deps: Vec<String>,
+            snippets: Vec<String>,
+        }
+        fn branch(platforms: &mut Vec<PlatformBranch>, platform: UnrealPlatform) -> &mut PlatformBranch {
+            match platforms.iter().position(|b| b.platform == Some(platform)) {
+                Some(i) => &mut platforms[i],
+                None => {
+                    platforms.push(PlatformBranch { platform: Some(platform), ..Default::default() });
+                    platforms.last_mut().unwrap()
+                }
+            }
+        }
+        // `LibArch` is the folder of the architecture being built, for
+        // platforms whose targets are staged into several folders
+        let lib_arch = |platform: UnrealPlatform| match platform {
+            UnrealPlatform::Win64 => Some("Target.Architecture == UnrealArch.Arm64 ? \"arm64\" : \"x64\""),
+            // Path.Combine skips the empty folder
+            UnrealPlatform::IOS | UnrealPlatform::TVOS
+                if targets.iter().any(|t| t.target.platform == platform && t.target.is_simulator()) =>
+            {
+                Some(if platform == UnrealPlatform::IOS {
+                    "Target.Architecture == UnrealArch.IOSSimulator ? \"Simulator\" : \"\""
+                } else {
+                    "Target.Architecture == UnrealArch.TVOSSimulator ? \"Simulator\" : \"\""
+                })
+            }
+            _ => None,
+        };
+        // C# expressions of the folders a target's libraries are in
+        let stage_dir = |target: &UnrealTarget| {
+            let mut dir = target.stage_dir_components().iter().map(|p| format!("\"{}\"", p)).collect::<Vec<_>>();
+            if lib_arch(target.platform).is_some() {
+                dir.truncate(1);
+                dir.push("LibArch".to_string());
+            }
+            if variants.is_some() {
+                dir.push("LibVariant".to_string());
+            }
+            dir
+        };
+        let module_path = |dir: &[String], filename: &str| {
+            format!("Path.Combine(ModuleDirectory, {}, \"{}\")", dir.join(", "), filename)
+        };
+        let has_default_module = !matches!(module.sources, ModuleCppSources::WithoutDefaultModule(_));
+        let mut platforms: Vec<PlatformBranch> = vec![];
+        if !module.external_dylibs.is_empty() {
+            for StagedTarget { target, .. } in targets {
+                let branch = branch(&mut platforms, target.platform);
+                let dir = stage_dir(target);
+                if let Some(lib_arch) = lib_arch(target.platform).filter(|_| branch.locals.is_empty()) {
+                    branch.locals.push(format!("string LibArch = {};", lib_arch));
+                }
+                if target.platform.is_desktop() && has_default_module && branch.priv_defs.is_empty() {
+                    // where the default module's StartupModule loads the libraries from
+                    let lib_dir = dir.join(" + \"/\" + ");
+                    branch.priv_defs.push(format!("\"RUST_LIB_DIR=\\\"\" + {} + \"\\\"\"", lib_dir));
+                    branch.priv_deps.push("Projects");
+                }
+                for dylib in module.external_dylibs {
+                    let dylib_filename = target.dylib_filename(dylib);
+                    let lib = module_path(&dir, &target.link_filename(dylib));
+                    let runtime_dep = module_path(&dir, &dylib_filename);
+                    // several architectures can share one (universal) library
+                    if branch.runtime_deps.contains(&runtime_dep) {
+                        continue;
+                    }
+                    branch.libs.push(lib);
+                    if target.platform == UnrealPlatform::Win64 {
+                        branch.delay_load.push(dylib_filename);
+                    }
+                    branch.runtime_deps.push(runtime_dep);
+                }
+            }
+        }
+        if let Some(android) = module.android.as_ref() {
+            let branch = branch(&mut platforms, UnrealPlatform::Android);
+            branch.receipts.push(("AndroidPlugin", "BaseAPL.xml"));
+            if android.jni_bootstrap.is_some() {
+                // FAndroidApplication and FJavaWrapper
+                branch.priv_deps.push("Launch");
+            }
+        }
+        if module.ios.is_some() {
+            branch(&mut platforms, UnrealPlatform::IOS).receipts.push(("IOSPlugin", "IOS_UPL.xml"));
+        }
+        for snippet in module.build_options.snippets {
+            if let CsHook::Platform(platform) = snippet.hook {
+                branch(&mut platforms, platform);
+            }
+        }
+        for branch in platforms.iter_mut() {
+            branch.snippets = module.build_options.snippets(CsHook::Platform(branch.platform.unwrap()), 12);
+        }
+
+        // `LibVariant` is the staging folder of the configuration being built
+        let lib_variant = match variants {
+            Some(profiles) if platforms.iter().any(|b| !b.libs.is_empty()) => {
+                let dirs = profiles.dirs();
+                let mut out = vec![format!("string LibVariant = \"{}\";", dirs[0].0)];
+                for (dir, configurations) in &dirs[1..] {
+                    let conds = configurations.iter().map(|c| Cond::Configuration(*c)).collect::<Vec<_>>();
+                    out.push(format!("if ({}) {{", Cond::Or(&conds)));
+                    out.push(format!("    LibVariant = \"{}\";", dir));
+                    if configurations.contains(&TargetConfiguration::Debug) {
+                        out.push(format!("    PublicDefinitions.Add(\"{}\");", DEBUG_BUILD));
+                    }
+                    out.push("}".to_string());
+                }
+                out
+            }
+            _ => vec![],
+        };
+
+        #[derive(Template, Serialize)]
         #[template(path = "Default.build.cs.jinja", escape = "none")]
         struct BuildTemplate<'a> {
             module_name: &'a str,
@@ -633,12 +1759,20 @@ impl<'a> Builder<'a> {
             priv_inc: &'a str,
             pub_defs: &'a[String],
             priv_defs: &'a[String],
-            dylibs: &'a[&'a str],
-            debug: bool,
+            options: &'a[String],
+            conditionals: &'a[ConditionalBlock],
+            lib_variant: &'a[String],
+            platforms: &'a[PlatformBranch],
+            usings: &'a[&'a str],
+            before_deps: &'a[String],
+            after_deps: &'a[String],
+            constructor_end: &'a[String],
+            methods: &'a[String],
+            optimization: &'a[(String, Vec<String>)],
         }
 
-        Ok(BuildTemplate {
-            module_name,
+        templates.render("Default.build.cs.jinja", &BuildTemplate {
+            module_name: module.name,
             pub_deps: &pub_deps,
             priv_deps_ed: &priv_deps_ed,
             priv_deps: &priv_deps,
@@ -646,14 +1780,22 @@ impl<'a> Builder<'a> {
             priv_inc: &priv_inc,
             pub_defs: &pub_defs,
             priv_defs: &priv_defs,
-            dylibs,
-            debug, 
-        }.render().unwrap())
+            options: &module.build_options.assignments(),
+            conditionals: &conditionals,
+            lib_variant: &lib_variant,
+            platforms: &platforms,
+            usings: module.build_options.usings,
+            before_deps: &module.build_options.snippets(CsHook::BeforeDeps, 8),
+            after_deps: &module.build_options.snippets(CsHook::AfterDeps, 8),
+            constructor_end: &module.build_options.snippets(CsHook::ConstructorEnd, 8),
+            methods: &module.build_options.methods.iter().map(|m| indent(m, 4)).collect::<Vec<_>>(),
+            optimization: &module.build_options.optimization_blocks(),
+        })
     }
 
     fn write_icon(icon_bytes: Option<&[u8]>, mut out: impl Write) -> Result<()> {
         // data for an empty 214x183 png file
-        let bytes = icon_bytes.unwrap_or_else(|| include_bytes!("../Icon128.png"));
+        let bytes = icon_bytes.unwrap_or(include_bytes!("../Icon128.png"));
         out.write_all(bytes).unwrap();
         Ok(())
     }
@@ -694,10 +1836,64 @@ impl<'a> Builder<'a> {
         self.plugin.rs_out_dir = Some(rs_out_dir.into());
         self
     }
+    /// Adds a cargo target triple the plugin supports, without staging any
+    /// libraries for it. If no targets are added, `build.cs` gets branches for
+    /// [`DEFAULT_TARGETS`].
+    pub fn target(mut self, triple: &'a str) -> Self {
+        self.targets.push((triple, None, None));
+        self
+    }
+    /// Adds a cargo target triple and copies every module's `external_dylibs`
+    /// for it from `lib_dir` (e.g. `target/aarch64-linux-android/release`)
+    /// into the module's platform folder.
+    ///
+    /// Staging both `aarch64-apple-darwin` and `x86_64-apple-darwin` produces
+    /// a single universal dylib in the `Mac` folder.
+    pub fn stage(mut self, triple: &'a str, lib_dir: &'a Path) -> Self {
+        self.targets.push((triple, Some(lib_dir), None));
+        self
+    }
+    /// Like [`stage`](Self::stage), for the libraries built with a cargo
+    /// `profile`. Enables [`library_variants`](Self::library_variants).
+    pub fn stage_profile(mut self, triple: &'a str, profile: &'a str, lib_dir: &'a Path) -> Self {
+        self.targets.push((triple, Some(lib_dir), Some(profile)));
+        self.variants = true;
+        self
+    }
+    /// Which cargo profile each UBT configuration uses, for library variants
+    /// and pre-build steps.
+    pub fn cargo_profiles(mut self, profiles: CargoProfiles<'a>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+    /// Stages libraries into a folder per cargo profile (`Win64/x64/debug`,
+    /// `Win64/x64/release`) and links the one matching `Target.Configuration`.
+    ///
+    /// Libraries must then be staged with [`stage_profile`](Self::stage_profile),
+    /// `generate` returns [`Error::MissingProfile`] otherwise.
+    pub fn library_variants(mut self) -> Self {
+        self.variants = true;
+        self
+    }
+    /// Makes UBT run `cargo build` for the plugin's targets before building it.
+    pub fn pre_build_steps(mut self, steps: PreBuildSteps<'a>) -> Self {
+        self.pre_build_steps = Some(steps);
+        self
+    }
     pub fn icon(mut self, bytes: &'a [u8]) -> Self {
         self.plugin.icon = Some(bytes);
         self
     }
+    /// Templates in `dir` replace the built-in ones of the same name.
+    pub fn templates(mut self, dir: &'a Path) -> Self {
+        self.plugin.templates = TemplateOverrides { dir: Some(dir) };
+        self
+    }
+    /// Adds sections to an ini file in the plugin's `Config` directory.
+    pub fn config(mut self, file: ConfigFile<'a>, sections: &'a [IniSection<'a>]) -> Self {
+        self.config.push((file, sections));
+        self
+    }
     pub fn docs_url(mut self, url: &'a str) -> Self {
         self.plugin.docs_url = url;
         self
@@ -761,11 +1957,52 @@ impl<'a> Builder<'a> {
         };
         std::fs::create_dir_all(&outdir).unwrap();
 
+        let targets = if self.targets.is_empty() {
+            DEFAULT_TARGETS.iter().map(|t| (*t, None, None)).collect()
+        } else {
+            self.targets
+        };
+        let targets = targets
+            .into_iter()
+            .map(|(triple, lib_dir, profile)| match UnrealTarget::from_triple(triple) {
+                Some(target) => Ok(StagedTarget { target, lib_dir, profile }),
+                None => Err(Error::UnknownTarget(triple.to_string())),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let variants = self.variants.then_some(&self.profiles);
+        // build.cs looks for the libraries in `<Platform>/<LibVariant>`
+        if let Some(staged) = targets.iter().find(|t| variants.is_some() && t.lib_dir.is_some() && t.profile.is_none()) {
+            return Err(Error::MissingProfile(staged.target.triple.to_string()));
+        }
+
+        let num_modules = self.plugin.modules.len();
+        let is_singular_module = |module: &Module| !(module.name != self.plugin.name || num_modules > 1);
+
         write_only_if_changed(
             &outdir.join(format!("{}.uplugin", self.plugin.name)),
             || {
-                let modules = self.modules.iter().map(ModuleProxy::from).collect::<Vec<_>>();
-                self.plugin.write_plugin(modules.as_slice())
+                let modules = self.modules
+                    .iter()
+                    .filter(|m| m.build_options.ty != ModuleType::External)
+                    .map(ModuleProxy::from)
+                    .collect::<Vec<_>>();
+                let pre_build_steps = match self.pre_build_steps.as_ref() {
+                    Some(steps) => {
+                        let mut unique_targets: Vec<UnrealTarget> = vec![];
+                        for staged in &targets {
+                            if !unique_targets.contains(&staged.target) {
+                                unique_targets.push(staged.target);
+                            }
+                        }
+                        let dylibs = self.modules
+                            .iter()
+                            .map(|m| (m.relative_dir(is_singular_module(m)), m.external_dylibs))
+                            .collect::<Vec<_>>();
+                        steps.to_json(&unique_targets, &dylibs, &self.profiles, self.variants)
+                    }
+                    None => String::new(),
+                };
+                self.plugin.write_plugin(modules.as_slice(), &pre_build_steps)
             },
         )?;
 
@@ -776,12 +2013,664 @@ impl<'a> Builder<'a> {
             Self::write_icon(self.plugin.icon, icon_file).unwrap();
         }
 
-        let num_modules = self.plugin.modules.len();
+        let mut config = ConfigFiles::default();
+        for module in self.modules.iter().filter(|m| m.build_options.ty != ModuleType::External) {
+            if let Some(settings) = module.settings.as_ref() {
+                config.add(self.plugin.name, ConfigFile::Plugin, &settings.ini_section(module.name), settings.ini_lines());
+            }
+        }
+        let packaged_files = self.modules
+            .iter()
+            .flat_map(|m| m.packaged_files(is_singular_module(m), &targets, variants))
+            .collect::<Vec<_>>();
+        if !packaged_files.is_empty() {
+            config.add(self.plugin.name, ConfigFile::FilterPlugin, "FilterPlugin", packaged_files);
+        }
+        for (file, sections) in &self.config {
+            config.add_sections(self.plugin.name, *file, sections);
+        }
+        for (path, contents) in config.render() {
+            let path = outdir.join("Config").join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            write_only_if_changed(&path, || Ok(contents))?;
+        }
+
         for module in self.modules {
-            let is_singular_module = !(module.name != self.plugin.name || num_modules > 1);
-            module.generate(&outdir, is_singular_module)?;
+            let is_singular_module = is_singular_module(&module);
+            module.generate(self.plugin.name, &outdir, is_singular_module, &targets, variants, self.plugin.templates)?;
         }
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{capi::CFunction, AndroidFeature, AndroidPermission, ConsoleVariable, PlistValue, Setting, SettingValue};
+    use std::path::PathBuf;
+
+    fn module(name: &'static str, external_dylibs: &'static [&'static str]) -> Module<'static> {
+        Module {
+            name,
+            android: None,
+            ios: None,
+            rust_api: None,
+            subsystem: None,
+            settings: None,
+            log: None,
+            pub_dep_mods: &["Core"],
+            priv_dep_mods: &[],
+            pub_include_paths: &[],
+            priv_include_paths: &[],
+            priv_defs: &[],
+            pub_defs: &[],
+            conditionals: &[],
+            whitelist_platforms: &[],
+            external_dylibs,
+            ty: HostType::Runtime,
+            loading_phase: LoadingPhase::Default,
+            sources: ModuleCppSources::WithDefaultModule(vec![]),
+            build_options: BuildOptions::default(),
+        }
+    }
+
+    /// An empty directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ueplugingen-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// The files `RuntimeDependencies.Add(Path.Combine(ModuleDirectory, ...))`
+    /// points at, for every value `LibVariant` can take and `LibArch` = `arch`.
+    fn runtime_dependencies(build_cs: &str, arch: &str) -> Vec<PathBuf> {
+        let mut variants = build_cs
+            .lines()
+            .filter_map(|l| l.trim().strip_prefix("string LibVariant = ").or_else(|| l.trim().strip_prefix("LibVariant = ")))
+            .map(|v| v.trim_end_matches(';').trim_matches('"').to_string())
+            .collect::<Vec<_>>();
+        if variants.is_empty() {
+            variants.push(String::new());
+        }
+        let mut out = vec![];
+        for line in build_cs.lines() {
+            let Some(args) = line.trim().strip_prefix("RuntimeDependencies.Add(Path.Combine(ModuleDirectory, ") else { continue };
+            let args = args.trim_end_matches("));").split(", ").collect::<Vec<_>>();
+            for variant in &variants {
+                out.push(args
+                    .iter()
+                    .map(|a| match *a {
+                        "LibVariant" => variant.as_str(),
+                        "LibArch" => arch,
+                        a => a.trim_matches('"'),
+                    })
+                    .collect());
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn library_variants_are_staged_where_build_cs_looks() {
+        let dir = temp_dir("variants");
+        let (release, debug) = (dir.join("release"), dir.join("debug"));
+        for lib_dir in [&release, &debug] {
+            std::fs::create_dir_all(lib_dir).unwrap();
+            std::fs::write(lib_dir.join("foo.dll"), b"").unwrap();
+            std::fs::write(lib_dir.join("foo.dll.lib"), b"").unwrap();
+        }
+        let out = dir.join("out");
+        Builder::new("Foo")
+            .out_dir(&out)
+            .stage_profile("x86_64-pc-windows-msvc", "release", &release)
+            .stage_profile("x86_64-pc-windows-msvc", "dev", &debug)
+            .module(module("Foo", &["foo"]))
+            .generate()
+            .unwrap();
+
+        let module_dir = out.join("Foo/Source");
+        let build_cs = std::fs::read_to_string(module_dir.join("Foo.build.cs")).unwrap();
+        let deps = runtime_dependencies(&build_cs, "x64");
+        assert_eq!(deps, [PathBuf::from("Win64/x64/release/foo.dll"), PathBuf::from("Win64/x64/debug/foo.dll")]);
+        for dep in deps {
+            assert!(module_dir.join(&dep).is_file(), "{}", dep.display());
+        }
+        assert!(build_cs.contains("    LibVariant = \"debug\";\r\n            PublicDefinitions.Add(\"DEBUG_BUILD=1\");"), "{build_cs}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn windows_architectures_are_staged_apart() {
+        let dir = temp_dir("win-archs");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("foo.dll"), b"").unwrap();
+        std::fs::write(dir.join("foo.dll.lib"), b"").unwrap();
+        let out = dir.join("out");
+        Builder::new("Foo")
+            .out_dir(&out)
+            .stage("x86_64-pc-windows-msvc", &dir)
+            .stage("aarch64-pc-windows-msvc", &dir)
+            .module(module("Foo", &["foo"]))
+            .generate()
+            .unwrap();
+
+        let module_dir = out.join("Foo/Source");
+        let build_cs = std::fs::read_to_string(module_dir.join("Foo.build.cs")).unwrap();
+        for arch in ["x64", "arm64"] {
+            let deps = runtime_dependencies(&build_cs, arch);
+            assert_eq!(deps, [PathBuf::from(format!("Win64/{arch}/foo.dll"))]);
+            assert!(module_dir.join(&deps[0]).is_file(), "{}", deps[0].display());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn simulators_are_staged_apart() {
+        let dir = temp_dir("ios-sim");
+        let image = |cputype: u32| {
+            let mut image = 0xfeedfacfu32.to_le_bytes().to_vec();
+            image.extend(cputype.to_le_bytes());
+            image.extend([0; 8]);
+            image
+        };
+        let triples = [("aarch64-apple-ios", 0x0100_000c), ("aarch64-apple-ios-sim", 0x0100_000c), ("x86_64-apple-ios", 0x0100_0007)];
+        for (triple, cputype) in triples {
+            std::fs::create_dir_all(dir.join(triple)).unwrap();
+            std::fs::write(dir.join(triple).join("libfoo.dylib"), image(cputype)).unwrap();
+        }
+        let out = dir.join("out");
+        let lib_dirs = triples.map(|(triple, _)| dir.join(triple));
+        let mut builder = Builder::new("Foo").out_dir(&out);
+        for ((triple, _), lib_dir) in triples.iter().zip(&lib_dirs) {
+            builder = builder.stage(triple, lib_dir);
+        }
+        builder.module(module("Foo", &["foo"])).generate().unwrap();
+
+        let module_dir = out.join("Foo/Source");
+        let build_cs = std::fs::read_to_string(module_dir.join("Foo.build.cs")).unwrap();
+        assert!(build_cs.contains("string LibArch = Target.Architecture == UnrealArch.IOSSimulator ? \"Simulator\" : \"\";"), "{build_cs}");
+        assert_eq!(runtime_dependencies(&build_cs, "Simulator"), [PathBuf::from("IOS/Simulator/libfoo.dylib")]);
+        assert_eq!(std::fs::read(module_dir.join("IOS/libfoo.dylib")).unwrap(), image(0x0100_000c));
+        let simulator = std::fs::read(module_dir.join("IOS/Simulator/libfoo.dylib")).unwrap();
+        assert_eq!(simulator[..8], [0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn overrides_get_the_builtin_context() {
+        let dir = temp_dir("template-context");
+        let templates = dir.join("templates");
+        std::fs::create_dir_all(&templates).unwrap();
+        let overrides = [
+            ("Default.uplugin.jinja", "{{ friendly_name }} {{ version }}{% for m in modules %} {{ m.name }}:{{ m.ty }}:{{ m.loading_phase }}{% endfor %}"),
+            ("Default.build.cs.jinja", "{{ module_name }} [{{ priv_deps }}]{% for branch in platforms %} {{ branch.platform }}={{ branch.libs | join(',') }}{% endfor %}{% for c in conditionals %} {{ c.cond }}{% endfor %}"),
+            ("DefaultModule.h.jinja", "{{ name }}"),
+            ("DefaultModule.cpp.jinja", "{{ filename }} {{ hooks.startup | length }} {{ hooks.shutdown | length }}"),
+            ("Subsystem.h.jinja", "{{ name }} {{ base_class }}"),
+            ("Subsystem.cpp.jinja", "{{ declare | length }} {{ init }} {{ shutdown is none }}"),
+            ("BaseAPL.xml.jinja", "{{ java_dir }} {{ dylibs | join(',') }} {{ permissions | length }} {{ gradle_dependencies | join(',') }}"),
+            ("IOS_UPL.xml.jinja", "{{ plist | length }} {{ plist[0] }}"),
+            ("Default.entitlements.jinja", "{% for line in entitlements %}{{ line }}{% endfor %}"),
+            ("RustApi.h.jinja", "{{ rust_api.wrappers }}{% for f in api.functions %} {{ f.ret }} {{ f.name }}({% for p, ty in f.params %}{% if not loop.first %}, {% endif %}{{ ty }} {{ p }}{% endfor %});{% endfor %}"),
+            ("Log.h.jinja", "{{ log.category }} {{ log.verbosity }} {% for cvar in log.console_variables %}{{ cvar.name }}={{ cvar.value }}{% endfor %}"),
+            ("Settings.h.jinja", "{{ plugin }} {% for s in settings.settings %}{{ s.name }}={{ s.value }} {% endfor %}{{ settings.display_name is none }}"),
+        ];
+        for (name, first_line) in overrides {
+            std::fs::write(templates.join(name), format!("{first_line}\n{{{{ builtin }}}}\n")).unwrap();
+        }
+        let java = dir.join("Foo.java");
+        std::fs::write(&java, "package com.foo;\n").unwrap();
+        let lib_rs = dir.join("lib.rs");
+        std::fs::write(&lib_rs, "#[no_mangle]\npub extern \"C\" fn foo_init() {}\n#[no_mangle]\npub extern \"C\" fn foo_rename(name: *const c_char, len: usize) {}\n").unwrap();
+        Builder::new("Foo")
+            .out_dir(&dir)
+            .templates(&templates)
+            .target("x86_64-pc-windows-msvc")
+            .target("aarch64-linux-android")
+            .module(Module {
+                android: Some(AndroidConfig {
+                    java_sources: &[java.as_path()],
+                    permissions: &[AndroidPermission::new("android.permission.INTERNET")],
+                    gradle_dependencies: &["com.foo:bar:1.0"],
+                    ..Default::default()
+                }),
+                ios: Some(IosConfig {
+                    background_modes: &["audio"],
+                    entitlements: &[("aps-environment", PlistValue::String("production"))],
+                    ..Default::default()
+                }),
+                rust_api: Some(RustApi { wrappers: true, ..RustApi::new(&lib_rs) }),
+                subsystem: Some(Subsystem { kind: SubsystemKind::Engine, init: Some("foo_init"), shutdown: None }),
+                log: Some(ModuleLog {
+                    console_variables: &[ConsoleVariable { name: "foo.Retries", value: SettingValue::Int(3), help: "" }],
+                    ..ModuleLog::new("LogFoo")
+                }),
+                settings: Some(DeveloperSettings {
+                    settings: &[Setting { name: "bVerbose", value: SettingValue::Bool(true), category: "Debug", tooltip: "" }],
+                    ..Default::default()
+                }),
+                conditionals: &[Conditional { priv_dep_mods: &["Slate"], ..Conditional::new(Cond::Editor) }],
+                ..module("Foo", &["foo"])
+            })
+            .generate()
+            .unwrap();
+        let first_line = |path: &str| {
+            let contents = std::fs::read_to_string(dir.join(path)).unwrap();
+            contents.lines().next().unwrap_or_default().to_string()
+        };
+        for (path, expected) in [
+            ("Foo/Foo.uplugin", "Foo 1 Foo:Runtime:Default"),
+            ("Foo/Source/Foo.build.cs", "Foo [\"CoreUObject\",\"Engine\",\"DeveloperSettings\"] Win64=Path.Combine(ModuleDirectory, \"Win64\", LibArch, \"foo.dll.lib\") Android=Path.Combine(ModuleDirectory, \"Android\", \"arm64-v8a\", \"libfoo.so\") IOS= Target.bBuildEditor"),
+            ("Foo/Source/Public/FooModule.h", "Foo"),
+            ("Foo/Source/Private/FooModule.cpp", "FooModule 1 1"),
+            ("Foo/Source/Public/FooSubsystem.h", "Foo UEngineSubsystem"),
+            ("Foo/Source/Private/FooSubsystem.cpp", "0 foo_init true"),
+            ("Foo/Source/BaseAPL.xml", "Android/Java foo 1 com.foo:bar:1.0"),
+            ("Foo/Source/IOS_UPL.xml", "2 <key>UIBackgroundModes</key>"),
+            ("Foo/Source/IOS/Foo.entitlements", "<key>aps-environment</key><string>production</string>"),
+            ("Foo/Source/Public/FooRustApi.h", "true void foo_init(); void foo_rename(const ANSICHAR* name, SIZE_T len);"),
+            ("Foo/Source/Public/FooLog.h", "LogFoo Log foo.Retries=3"),
+            ("Foo/Source/Public/FooSettings.h", "Foo bVerbose=true true"),
+        ] {
+            assert_eq!(first_line(path), expected, "{path}");
+        }
+        // followed by the built-in output
+        let header = std::fs::read_to_string(dir.join("Foo/Source/Public/FooModule.h")).unwrap();
+        assert!(header.contains("class FFooModule : public IModuleInterface"), "{header}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn generated_files_can_be_overridden() {
+        let dir = temp_dir("file-templates");
+        std::fs::write(dir.join("lib.rs"), "#[no_mangle]\npub extern \"C\" fn foo_init() {}\n").unwrap();
+        std::fs::write(dir.join("RustApi.h.jinja"), "// {{ name }} API\n{{ builtin }}\n").unwrap();
+        let lib_rs = dir.join("lib.rs");
+        Builder::new("Foo")
+            .out_dir(&dir)
+            .templates(&dir)
+            .module(Module { rust_api: Some(RustApi::new(&lib_rs)), ..module("Foo", &[]) })
+            .generate()
+            .unwrap();
+        let header = std::fs::read_to_string(dir.join("Foo/Source/Public/FooRustApi.h")).unwrap();
+        assert!(header.starts_with("// Foo API\n"), "{header}");
+        assert!(header.contains("void foo_init(void);"), "{header}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn library_variants_need_a_profile() {
+        let dir = temp_dir("no-profile");
+        let result = Builder::new("Foo")
+            .out_dir(&dir)
+            .stage("x86_64-pc-windows-msvc", &dir)
+            .library_variants()
+            .module(module("Foo", &["foo"]))
+            .generate();
+        assert!(matches!(result, Err(Error::MissingProfile(t)) if t == "x86_64-pc-windows-msvc"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn subsystem_functions_come_from_the_rust_api() {
+        let subsystem = Subsystem { kind: SubsystemKind::World, init: Some("foo_init"), shutdown: Some("foo_shutdown") };
+        let templates = TemplateOverrides::default();
+        let (_, items) = subsystem.sources("Foo", None, templates).unwrap();
+        let CppItem::Source(source) = &items[1] else { panic!() };
+        assert!(source.contents.contains("extern \"C\" {\nvoid foo_init();\nvoid foo_shutdown();\n}"), "{}", source.contents);
+
+        let function = |name: &str| CFunction { name: name.to_string(), params: vec![], ret: CType::Void, docs: vec![] };
+        let mut api = CApi { functions: vec![function("foo_init"), function("foo_shutdown")], ..CApi::default() };
+        let (_, items) = subsystem.sources("Foo", Some(&api), templates).unwrap();
+        let CppItem::Source(source) = &items[1] else { panic!() };
+        assert!(source.contents.contains("#include \"FooRustApi.h\""), "{}", source.contents);
+        assert!(!source.contents.contains("extern \"C\""), "{}", source.contents);
+
+        api.functions.pop();
+        let result = subsystem.sources("Foo", Some(&api), templates);
+        assert!(matches!(result, Err(Error::UnknownFunction(f)) if f == "foo_shutdown"));
+    }
+
+    #[test]
+    fn packaged_files_cover_unstaged_targets() {
+        let target = |triple| StagedTarget { target: UnrealTarget::from_triple(triple).unwrap(), lib_dir: None, profile: None };
+        let targets = [target("x86_64-pc-windows-msvc"), target("aarch64-linux-android")];
+        let module = module("Foo", &["foo"]);
+        assert_eq!(module.packaged_files(true, &targets, None), [
+            "/Source/Win64/x64/foo.dll",
+            "/Source/Win64/x64/foo.dll.lib",
+            "/Source/Android/arm64-v8a/libfoo.so",
+        ]);
+        let profiles = CargoProfiles::default();
+        assert_eq!(module.packaged_files(true, &targets[1..], Some(&profiles)), [
+            "/Source/Android/arm64-v8a/release/libfoo.so",
+            "/Source/Android/arm64-v8a/debug/libfoo.so",
+        ]);
+    }
+
+    #[test]
+    fn unoptimized_modules_define_debug_build() {
+        let mut module = module("Foo", &[]);
+        module.build_options.optimize_code = Some(CodeOptimization::Never);
+        let build_cs = Builder::write_build(&module, &[], None, TemplateOverrides::default()).unwrap();
+        assert!(build_cs.contains("PublicDefinitions.Add(\"DEBUG_BUILD=1\");"), "{build_cs}");
+
+        module.build_options.optimize_code = None;
+        module.build_options.optimize_code_per_configuration = &[(TargetConfiguration::Debug, CodeOptimization::Never)];
+        let build_cs = Builder::write_build(&module, &[], None, TemplateOverrides::default()).unwrap();
+        assert!(build_cs.contains("OptimizeCode = CodeOptimization.Never;\r\n            PublicDefinitions.Add(\"DEBUG_BUILD=1\");"), "{build_cs}");
+    }
+
+    #[test]
+    fn build_options() {
+        let mut module = module("Foo", &[]);
+        let build_cs = |module: &Module| Builder::write_build(module, &[], None, TemplateOverrides::default()).unwrap().replace("\r\n", "\n");
+        assert!(build_cs(&module).contains("\n        PCHUsage = ModuleRules.PCHUsageMode.UseExplicitOrSharedPCHs;\n\n"), "{}", build_cs(&module));
+
+        module.build_options = BuildOptions {
+            pch_usage: PCHUsageMode::NoPCHs,
+            private_pch_header_file: Some("Private/FooPCH.h"),
+            enable_exceptions: Some(true),
+            use_rtti: Some(false),
+            cpp_standard: Some(CppStandardVersion::Cpp20),
+            use_unity: Some(false),
+            shadow_variable_warning_level: Some(WarningLevel::Error),
+            warnings_as_errors: Some(true),
+            iwyu_support: Some(IWYUSupport::Full),
+            legacy_public_include_paths: Some(false),
+            optimize_code: Some(CodeOptimization::InShippingBuildsOnly),
+            ..Default::default()
+        };
+        assert!(build_cs(&module).contains(&[
+            "        PCHUsage = ModuleRules.PCHUsageMode.NoPCHs;",
+            "        PrivatePCHHeaderFile = \"Private/FooPCH.h\";",
+            "        bEnableExceptions = true;",
+            "        bUseRTTI = false;",
+            "        CppStandard = CppStandardVersion.Cpp20;",
+            "        bUseUnity = false;",
+            "        ShadowVariableWarningLevel = WarningLevel.Error;",
+            "        bWarningsAsErrors = true;",
+            "        IWYUSupport = IWYUSupport.Full;",
+            "        bLegacyPublicIncludePaths = false;",
+            "        OptimizeCode = CodeOptimization.InShippingBuildsOnly;\n",
+        ].join("\n")), "{}", build_cs(&module));
+
+        // external modules ignore the C++ options
+        module.build_options.ty = ModuleType::External;
+        let external = build_cs(&module);
+        assert!(external.contains("\n        Type = ModuleType.External;\n"), "{external}");
+        assert!(!external.contains("PCHUsage") && !external.contains("bUseRTTI"), "{external}");
+    }
+
+    #[test]
+    fn base_apl() {
+        let android = AndroidConfig {
+            proguard_additions: &["-keep class com.foo.** { *; }"],
+            gradle_dependencies: &["com.foo:bar:1.0"],
+            build_gradle_additions: &["android { ndkVersion '25.1' }"],
+            application_meta_data: &[("com.foo.key", "a&b")],
+            activity_meta_data: &[("com.foo.mode", "vr")],
+            game_activity_import_additions: &["import com.foo.Bar;"],
+            game_activity_class_additions: &["public void foo() {}"],
+            ..Default::default()
+        };
+        let apl = Builder::write_base_apl(&android, &["foo"], Some(&CargoProfiles::default()), TemplateOverrides::default())
+            .unwrap()
+            .replace("\r\n", "\n");
+        for expected in [
+            "    <setString result=\"LibVariant\" value=\"release\"/>\n    <setBoolIsEqual result=\"bIsDebug\" arg1=\"$S(Configuration)\" arg2=\"Debug\"/>",
+            "    <copyFile src=\"$S(PluginDir)/Android/$S(Architecture)/$S(LibVariant)/libfoo.so\"",
+            "    <loadLibrary name=\"foo\" failmsg=\"Did not load libfoo library\" />",
+            "    <addElements tag=\"application\">\n        <meta-data android:name=\"com.foo.key\" android:value=\"a&amp;b\"/>\n    </addElements>",
+            // the activity is com.epicgames.ue4.GameActivity before UE5
+            "        <setBoolOr result=\"bIsGameActivity\" arg1=\"$B(bIsGameActivity4)\" arg2=\"$B(bIsGameActivity5)\"/>\n        <if condition=\"bIsGameActivity\">",
+            "                <addElements tag=\"$\">\n                    <meta-data android:name=\"com.foo.mode\" android:value=\"vr\"/>",
+            "<proguardAdditions>\n    <insert>\n-keep class com.foo.** { *; }\n    </insert>\n</proguardAdditions>",
+            "    <insert>\ndependencies {\n    implementation 'com.foo:bar:1.0'\n}\nandroid { ndkVersion &apos;25.1&apos; }\n    </insert>",
+            "<gameActivityImportAdditions>\n    <insert>\nimport com.foo.Bar;\n",
+            "<gameActivityClassAdditions>\n    <insert>\npublic void foo() {}\n",
+        ] {
+            assert!(apl.contains(expected), "{expected} missing from {apl}");
+        }
+
+        // sections without content are left out
+        let apl = Builder::write_base_apl(&AndroidConfig::default(), &[], None, TemplateOverrides::default()).unwrap();
+        for section in ["<soLoadLibrary>", "<addElements", "<loopElements", "<proguardAdditions>", "<buildGradleAdditions>", "<gameActivity", "LibVariant"] {
+            assert!(!apl.contains(section), "{section} in {apl}");
+        }
+    }
+
+    #[test]
+    fn java_sources_and_jni_bootstrap() {
+        let dir = temp_dir("java");
+        let bridge = dir.join("Bridge.java");
+        std::fs::write(&bridge, "// bridge\npackage com.foo.bridge;\n\npublic class Bridge {}\n").unwrap();
+        let java_sources = [bridge.as_path()];
+        let out = dir.join("out");
+        Builder::new("Foo")
+            .out_dir(&out)
+            .target("aarch64-linux-android")
+            .module(Module {
+                android: Some(AndroidConfig { java_sources: &java_sources, jni_bootstrap: Some("foo_jni_init"), ..Default::default() }),
+                ..module("Foo", &[])
+            })
+            .generate()
+            .unwrap();
+
+        let module_dir = out.join("Foo/Source");
+        let staged = std::fs::read_to_string(module_dir.join("Android/Java/com/foo/bridge/Bridge.java")).unwrap();
+        assert!(staged.contains("public class Bridge {}"), "{staged}");
+        let apl = std::fs::read_to_string(module_dir.join("BaseAPL.xml")).unwrap().replace("\r\n", "\n");
+        assert!(apl.contains("    <copyDir src=\"$S(PluginDir)/Android/Java\" dst=\"$S(BuildDir)/src\" />\n</resourceCopies>"), "{apl}");
+        assert!(apl.contains("<gradleCopies>\n    <copyDir src=\"$S(PluginDir)/Android/Java\" dst=\"$S(BuildDir)/gradle/app/src/main/java\" />\n</gradleCopies>"), "{apl}");
+        let filter = std::fs::read_to_string(out.join("Foo/Config/FilterPlugin.ini")).unwrap();
+        assert!(filter.contains("/Source/Android/Java/..."), "{filter}");
+
+        let cpp = std::fs::read_to_string(module_dir.join("Private/FooModule.cpp")).unwrap();
+        for expected in [
+            "#if PLATFORM_ANDROID\n#include \"Android/AndroidApplication.h\"\n#include \"Android/AndroidJNI.h\"\n\nextern \"C\" void foo_jni_init(JavaVM* vm, jobject activity);\n#endif",
+            "    JavaVM* VM = nullptr;\n    FAndroidApplication::GetJavaEnv()->GetJavaVM(&VM);\n    foo_jni_init(VM, FJavaWrapper::GameActivityThis);\n#endif",
+        ] {
+            assert!(cpp.contains(expected), "{expected} missing from {cpp}");
+        }
+        let build_cs = std::fs::read_to_string(module_dir.join("Foo.build.cs")).unwrap().replace("\r\n", "\n");
+        let android = &build_cs[build_cs.find("if (Target.Platform == UnrealTargetPlatform.Android) {").unwrap()..];
+        assert!(android.contains("PrivateDependencyModuleNames.Add(\"Launch\");"), "{build_cs}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn android_modules_always_get_an_apl() {
+        let dir = temp_dir("permissions");
+        let permissions = [
+            AndroidPermission::new("INTERNET"),
+            AndroidPermission { max_sdk_version: Some(30), ..AndroidPermission::new("com.oculus.permission.HAND_TRACKING") },
+        ];
+        let features = [AndroidFeature { name: "android.hardware.vulkan.version", required: false, version: Some(0x401000) }];
+        // no libraries to load
+        Builder::new("Foo")
+            .out_dir(&dir)
+            .module(Module {
+                android: Some(AndroidConfig { permissions: &permissions, features: &features, ..Default::default() }),
+                ..module("Foo", &[])
+            })
+            .generate()
+            .unwrap();
+
+        let apl = std::fs::read_to_string(dir.join("Foo/Source/BaseAPL.xml")).unwrap().replace("\r\n", "\n");
+        assert!(apl.contains(&[
+            "<androidManifestUpdates>",
+            "    <addPermission android:name=\"android.permission.INTERNET\"/>",
+            "    <addPermission android:name=\"com.oculus.permission.HAND_TRACKING\" android:maxSdkVersion=\"30\"/>",
+            "    <addFeature android:name=\"android.hardware.vulkan.version\" android:required=\"false\" android:version=\"4198400\"/>",
+            "</androidManifestUpdates>",
+        ].join("\n")), "{apl}");
+        let build_cs = std::fs::read_to_string(dir.join("Foo/Source/Foo.build.cs")).unwrap();
+        assert!(build_cs.contains("AdditionalPropertiesForReceipt.Add(\"AndroidPlugin\", Path.Combine(ModuleDirectory, \"BaseAPL.xml\"));"), "{build_cs}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ios_upl_and_entitlements() {
+        let dir = temp_dir("ios");
+        Builder::new("Foo")
+            .out_dir(&dir)
+            .module(Module {
+                ios: Some(IosConfig {
+                    usage_descriptions: &[("NSMicrophoneUsageDescription", "Voice chat")],
+                    entitlements: &[("com.apple.developer.associated-domains", PlistValue::Array(&["applinks:example.com"]))],
+                    ..Default::default()
+                }),
+                ..module("Foo", &[])
+            })
+            .generate()
+            .unwrap();
+
+        let module_dir = dir.join("Foo/Source");
+        let upl = std::fs::read_to_string(module_dir.join("IOS_UPL.xml")).unwrap();
+        assert!(upl.contains(&[
+            "<iosPListUpdates>",
+            "    <addElements tag=\"dict\" once=\"true\">",
+            "        <key>NSMicrophoneUsageDescription</key>",
+            "        <string>Voice chat</string>",
+            "    </addElements>",
+            "</iosPListUpdates>",
+        ].join("\n")), "{upl}");
+        let entitlements = std::fs::read_to_string(module_dir.join("IOS/Foo.entitlements")).unwrap();
+        assert!(entitlements.contains(&[
+            "<dict>",
+            "    <key>com.apple.developer.associated-domains</key>",
+            "    <array><string>applinks:example.com</string></array>",
+            "</dict>",
+        ].join("\n")), "{entitlements}");
+        let filter = std::fs::read_to_string(dir.join("Foo/Config/FilterPlugin.ini")).unwrap();
+        assert!(filter.contains("/Source/IOS/Foo.entitlements"), "{filter}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn default_module_loads_the_libraries() {
+        let dir = temp_dir("dylib-loading");
+        Builder::new("Foo")
+            .out_dir(&dir)
+            .target("x86_64-pc-windows-msvc")
+            .target("aarch64-linux-android")
+            .module(module("Foo", &["foo", "bar"]))
+            .generate()
+            .unwrap();
+
+        let module_dir = dir.join("Foo/Source");
+        let build_cs = std::fs::read_to_string(module_dir.join("Foo.build.cs")).unwrap();
+        assert_eq!(build_cs.matches("PrivateDefinitions.Add(\"RUST_LIB_DIR=\\\"\" + \"Win64\" + \"/\" + LibArch + \"\\\"\");").count(), 1, "{build_cs}");
+        let cpp = std::fs::read_to_string(module_dir.join("Private/FooModule.cpp")).unwrap();
+        for expected in [
+            "#ifdef RUST_LIB_DIR\n#include \"HAL/PlatformProcess.h\"",
+            "static TArray<void*> LibHandles;\n#elif PLATFORM_WINDOWS\n#error \"Foo: no Rust libraries are staged for this platform\"\n#endif",
+            "#if defined(RUST_LIB_DIR) || PLATFORM_DESKTOP\nDEFINE_LOG_CATEGORY_STATIC(LogFoo, Log, All);\n#endif",
+            "#elif PLATFORM_DESKTOP\n    UE_LOG(LogFoo, Warning, TEXT(\"no Rust libraries are staged for this platform\"));\n#endif",
+            "const FString LibDir = FPaths::Combine(Plugin->GetBaseDir(), TEXT(\"Source\"), TEXT(RUST_LIB_DIR));",
+            "for (const TCHAR* Name : { TEXT(\"foo\"), TEXT(\"bar\") }) {",
+            "UE_LOG(LogFoo, Error, TEXT(\"failed to load %s\"), *Path);",
+            "void FFooModule::ShutdownModule() {\n#ifdef RUST_LIB_DIR\n    for (int32 i = LibHandles.Num() - 1; i >= 0; --i) {\n        FPlatformProcess::FreeDllHandle(LibHandles[i]);",
+        ] {
+            assert!(cpp.contains(expected), "{expected} missing from {cpp}");
+        }
+
+        // the default targets have no Linux triple, so a Linux build only warns
+        Builder::new("Baz").out_dir(&dir).module(module("Baz", &["baz"])).generate().unwrap();
+        let cpp = std::fs::read_to_string(dir.join("Baz/Source/Private/BazModule.cpp")).unwrap();
+        assert!(cpp.contains("#elif PLATFORM_MAC || PLATFORM_WINDOWS\n#error"), "{cpp}");
+
+        // without libraries the module has nothing to load
+        let mut module = module("Bar", &[]);
+        module.sources = ModuleCppSources::None;
+        Builder::new("Bar").out_dir(&dir).module(module).generate().unwrap();
+        let cpp = std::fs::read_to_string(dir.join("Bar/Source/Private/BarModule.cpp")).unwrap();
+        assert!(!cpp.contains("RUST_LIB_DIR") && cpp.contains("void FBarModule::StartupModule() { }"), "{cpp}");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rust_log_hook() {
+        let dir = temp_dir("rust-log");
+        let mut module = module("Foo", &["foo"]);
+        module.log = Some(ModuleLog { rust_log: Some("foo_set_logger"), ..ModuleLog::new("LogFoo") });
+        Builder::new("Foo").out_dir(&dir).target("x86_64-pc-windows-msvc").module(module).generate().unwrap();
+
+        let cpp = std::fs::read_to_string(dir.join("Foo/Source/Private/FooModule.cpp")).unwrap();
+        assert!(cpp.contains("#include \"FooLog.h\""), "{cpp}");
+        assert!(cpp.contains(
+            "extern \"C\" void foo_set_logger(void (*Callback)(uint8, const ANSICHAR*, SIZE_T, const ANSICHAR*, SIZE_T), uint8 MaxLevel);"
+        ), "{cpp}");
+        // installed once the libraries are loaded, removed before they are freed
+        let startup = cpp.find("    foo_set_logger(&FooRustLog, FooRustLogLevel());").expect(&cpp);
+        assert!(cpp.find("LibHandles.Add(").unwrap() < startup, "{cpp}");
+        let shutdown = cpp.find("    foo_set_logger(nullptr, 0);").expect(&cpp);
+        assert!(shutdown < cpp.find("FPlatformProcess::FreeDllHandle").unwrap(), "{cpp}");
+        assert!(dir.join("Foo/Source/Public/FooLog.h").exists() && dir.join("Foo/Source/Private/FooLog.cpp").exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn conds() {
+        let win = Cond::Platform(UnrealPlatform::Win64);
+        let shipping = Cond::Configuration(TargetConfiguration::Shipping);
+        assert_eq!(win.to_string(), "Target.Platform == UnrealTargetPlatform.Win64");
+        assert_eq!(Cond::TargetType(TargetType::Server).to_string(), "Target.Type == TargetType.Server");
+        assert_eq!(Cond::Not(&Cond::Editor).to_string(), "!Target.bBuildEditor");
+        assert_eq!(Cond::Not(&Cond::Not(&Cond::Editor)).to_string(), "!!Target.bBuildEditor");
+        assert_eq!(Cond::Not(&shipping).to_string(), "!(Target.Configuration == UnrealTargetConfiguration.Shipping)");
+        assert_eq!(Cond::And(&[]).to_string(), "true");
+        assert_eq!(Cond::Or(&[]).to_string(), "false");
+        assert_eq!(Cond::Or(&[Cond::Editor]).to_string(), "Target.bBuildEditor");
+        let either = [Cond::Editor, Cond::Not(&shipping)];
+        assert_eq!(
+            Cond::And(&[Cond::Or(&either), win]).to_string(),
+            "(Target.bBuildEditor || !(Target.Configuration == UnrealTargetConfiguration.Shipping)) && Target.Platform == UnrealTargetPlatform.Win64",
+        );
+        assert_eq!(Cond::Not(&Cond::Or(&either)).to_string(), format!("!({})", Cond::Or(&either)));
+    }
+
+    #[test]
+    fn cs_hooks() {
+        let mut module = module("Foo", &["foo"]);
+        module.build_options.snippets = &[
+            CsSnippet { name: "end", hook: CsHook::ConstructorEnd, code: "End();" },
+            CsSnippet { name: "linux", hook: CsHook::Platform(UnrealPlatform::Linux), code: "Linux();" },
+            CsSnippet { name: "win", hook: CsHook::Platform(UnrealPlatform::Win64), code: "\n    if (Win) {\n        Win();\n    }\n" },
+            CsSnippet { name: "after", hook: CsHook::AfterDeps, code: "After();" },
+            CsSnippet { name: "before", hook: CsHook::BeforeDeps, code: "Before();" },
+        ];
+        let targets = [StagedTarget { target: UnrealTarget::from_triple("x86_64-pc-windows-msvc").unwrap(), lib_dir: None, profile: None }];
+        let build_cs = Builder::write_build(&module, &targets, None, TemplateOverrides::default()).unwrap().replace("\r\n", "\n");
+        let position = |code: &str| build_cs.find(code).unwrap_or_else(|| panic!("{code} missing from {build_cs}"));
+
+        assert!(position("        // before\n        Before();") < position("PublicDependencyModuleNames.AddRange"));
+        assert!(position("PrivateDependencyModuleNames.AddRange") < position("        // after\n        After();"));
+        assert!(position("After();") < position("PublicIncludePaths.Add(ModuleDirectory)"));
+        // after the platform's own statements, and in a branch of its own without libraries
+        let win = position("if (Target.Platform == UnrealTargetPlatform.Win64) {");
+        assert!(win < position("RuntimeDependencies.Add") && position("RuntimeDependencies.Add") < position("            // win\n            if (Win) {\n                Win();\n            }"));
+        assert!(position("Win();") < position("} else if (Target.Platform == UnrealTargetPlatform.Linux) {\n            // linux\n            Linux();\n        }"));
+        assert!(position("Linux();") < position("        // end\n        End();\n    }\n}"));
+    }
+
+    #[test]
+    fn define_escaping() {
+        assert_eq!(c_escape("a \"b\" \\ c\nd\te\x01f"), r#"a \"b\" \\ c\nd\te\001f"#);
+        assert_eq!(c_escape("\x1b1"), r"\0331");
+
+        let define = Define::Str("GREETING", "say \"hi\"\\\n");
+        assert_eq!(define.to_definition().unwrap(), r#"GREETING="say \"hi\"\\\n""#);
+        // escaped again for the C# string holding it
+        assert_eq!(define.to_cs().unwrap(), r#"GREETING=\"say \\\"hi\\\"\\\\\\n\""#);
+        assert_eq!(Define::Raw("R", "\"x\"").to_cs().unwrap(), r#"R=\"x\""#);
+        assert_eq!(Define::Bool("B", true).to_definition().unwrap(), "B=1");
+        assert_eq!(Define::Int("I", -3).to_definition().unwrap(), "I=-3");
+        for name in ["", "1A", "A-B", "A B", "A\"B"] {
+            assert!(matches!(Define::Flag(name).to_definition(), Err(Error::InvalidDefine(_))), "{name}");
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! A `log::Log` writing records to a UE log category, for use in the Rust
+//! library of a module generated with [`ModuleLog::rust_log`](crate::ModuleLog::rust_log).
+
+use std::{
+    ffi::c_char,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+
+/// The callback the generated module installs, `level` being a
+/// `log::Level` as `u8`.
+pub type UnrealLogCallback = extern "C" fn(
+    level: u8,
+    target: *const c_char,
+    target_len: usize,
+    message: *const c_char,
+    message_len: usize,
+);
+
+/// Forwards records to the installed [`UnrealLogCallback`], dropping them
+/// while none is.
+pub struct UnrealLogger {
+    callback: RwLock<Option<UnrealLogCallback>>,
+}
+
+static LOGGER: UnrealLogger = UnrealLogger { callback: RwLock::new(None) };
+/// Whether `LOGGER` is the global `log` logger.
+static IS_GLOBAL: AtomicBool = AtomicBool::new(false);
+
+impl UnrealLogger {
+    /// Installs or, with `None`, removes the callback. The first install
+    /// also sets the logger as the global `log` logger, unless another one
+    /// already is.
+    ///
+    /// `max_level` is a `log::LevelFilter` as `u8`, from the category's
+    /// verbosity at startup. It becomes the `log` max level on every
+    /// install, as long as this is the global logger.
+    ///
+    /// Blocks until records being written finish, so the callback may be
+    /// unloaded once this returns.
+    pub fn set_callback(callback: Option<UnrealLogCallback>, max_level: u8) {
+        let installed = callback.is_some();
+        *LOGGER.callback.write().unwrap_or_else(|e| e.into_inner()) = callback;
+        if installed && log::set_logger(&LOGGER).is_ok() {
+            IS_GLOBAL.store(true, Ordering::Relaxed);
+        }
+        if installed && IS_GLOBAL.load(Ordering::Relaxed) {
+            log::set_max_level(level_filter(max_level));
+        }
+    }
+}
+
+impl log::Log for UnrealLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        self.callback.read().map(|c| c.is_some()).unwrap_or(false)
+    }
+
+    fn log(&self, record: &log::Record) {
+        let Ok(callback) = self.callback.read() else { return };
+        let Some(callback) = *callback else { return };
+        let message = record.args().to_string();
+        let target = record.target();
+        callback(
+            record.level() as u8,
+            target.as_ptr().cast(),
+            target.len(),
+            message.as_ptr().cast(),
+            message.len(),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+fn level_filter(level: u8) -> log::LevelFilter {
+    match level {
+        0 => log::LevelFilter::Off,
+        1 => log::LevelFilter::Error,
+        2 => log::LevelFilter::Warn,
+        3 => log::LevelFilter::Info,
+        4 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    }
+}
+
+/// Exports the function named in [`ModuleLog::rust_log`](crate::ModuleLog::rust_log)
+/// from the crate's library:
+///
+/// ```
+/// ueplugingen::unreal_log_bridge!(my_plugin_set_log_callback);
+/// ```
+#[macro_export]
+macro_rules! unreal_log_bridge {
+    ($name:ident) => {
+        #[no_mangle]
+        pub extern "C" fn $name(callback: Option<$crate::UnrealLogCallback>, max_level: u8) {
+            $crate::UnrealLogger::set_callback(callback, max_level);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn callback(_: u8, _: *const c_char, _: usize, _: *const c_char, _: usize) {}
+
+    #[test]
+    fn every_install_sets_the_max_level() {
+        UnrealLogger::set_callback(Some(callback), 2);
+        assert_eq!(log::max_level(), log::LevelFilter::Warn);
+        UnrealLogger::set_callback(None, 0);
+        assert_eq!(log::max_level(), log::LevelFilter::Warn);
+        UnrealLogger::set_callback(Some(callback), 4);
+        assert_eq!(log::max_level(), log::LevelFilter::Debug);
+        UnrealLogger::set_callback(None, 0);
+    }
+}
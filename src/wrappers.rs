@@ -0,0 +1,584 @@
+//! UE-flavoured C++ wrappers over a [`CApi`]: `FString` and `TArray<uint8>`
+//! parameters, and RAII classes for opaque handles.
+//!
+//! * `*const c_char` (optionally followed by its length, an integer named
+//!   `len` or `<ptr>_len`) is taken as an `FString`, passed as UTF-8.
+//! * `*const u8` followed by its length is taken as a `TArray<uint8>`.
+//! * An opaque type with a `*_free`/`*_destroy`/`*_drop`/`*_delete` function
+//!   becomes an `F<Module><Type>` class owning the pointer; functions taking it
+//!   first become methods, functions returning it static constructors.
+//! * A returned `*const c_char` is copied into an `FString`; a returned
+//!   `*mut c_char` is copied and given back to the crate's string free
+//!   function, if it has one.
+
+use super::{
+    capi::{push_docs, CApi, CFunction, CType},
+    BlueprintFunction, Error, Result,
+};
+
+const FREE_SUFFIXES: [&str; 4] = ["_free", "_destroy", "_drop", "_delete"];
+
+/// Members every handle class declares, which methods can't be named after.
+const HANDLE_MEMBERS: [&str; 5] = ["Handle", "IsValid", "Get", "Release", "Reset"];
+
+/// An opaque Rust type owned by a generated class.
+struct Handle<'a> {
+    ty: &'a str,
+    class: String,
+    free: &'a CFunction,
+}
+
+pub(crate) struct Wrappers<'a> {
+    api: &'a CApi,
+    api_macro: String,
+    handles: Vec<Handle<'a>>,
+    string_free: Option<&'a CFunction>,
+}
+
+/// How one C++ parameter is passed to the C function.
+struct Param {
+    decl: String,
+    /// Statements before the call.
+    setup: Vec<String>,
+    args: Vec<String>,
+    kind: ParamKind,
+}
+
+enum ParamKind {
+    /// Passed through as is.
+    Direct(CType),
+    String,
+    Bytes,
+    Handle,
+}
+
+impl<'a> Wrappers<'a> {
+    pub(crate) fn new(api: &'a CApi, module_name: &str) -> Self {
+        let owned = |f: &CFunction, ty: &CType| {
+            f.params.len() == 1 && f.ret == CType::Void && f.params[0].1 == *ty
+                && FREE_SUFFIXES.iter().any(|s| f.name.ends_with(s))
+        };
+        let handles = api
+            .opaque_types()
+            .into_iter()
+            .filter_map(|ty| {
+                let ptr = CType::Ptr { ty: Box::new(CType::Named(ty.to_string())), is_const: false };
+                let free = api.functions.iter().find(|f| owned(f, &ptr))?;
+                Some(Handle { ty, class: format!("F{}{}", module_name, ty), free })
+            })
+            .collect();
+        let string = CType::Ptr { ty: Box::new(CType::Char), is_const: false };
+        Self {
+            api,
+            api_macro: format!("{}_API", module_name.to_uppercase()),
+            handles,
+            string_free: api.functions.iter().find(|f| owned(f, &string)),
+        }
+    }
+
+    fn handle(&self, ty: &CType) -> Option<&Handle<'a>> {
+        match ty {
+            CType::Ptr { ty, .. } => match ty.as_ref() {
+                CType::Named(name) => self.handles.iter().find(|h| h.ty == name),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn is_free(&self, f: &CFunction) -> bool {
+        self.handles.iter().any(|h| std::ptr::eq(h.free, f)) || self.string_free.is_some_and(|s| std::ptr::eq(s, f))
+    }
+
+    /// The handle `f` is a method of.
+    fn receiver(&self, f: &CFunction) -> Option<&Handle<'a>> {
+        self.handle(&f.params.first()?.1)
+    }
+
+    /// The handle `f` constructs.
+    fn constructed(&self, f: &CFunction) -> Option<&Handle<'a>> {
+        match &f.ret {
+            CType::Ptr { is_const: false, .. } => self.handle(&f.ret),
+            _ => None,
+        }
+    }
+
+    /// UE style parameter name, not shadowing a type or the `Handle` member.
+    fn param_name(&self, name: &str) -> String {
+        let var = pascal_case(name);
+        let taken = var == "Handle"
+            || self.api.structs.iter().any(|s| s.name == var)
+            || self.api.enums.iter().any(|e| e.name == var)
+            || self.handles.iter().any(|h| h.ty == var || h.class == var);
+        if taken { format!("In{}", var) } else { var }
+    }
+
+    fn params(&self, params: &[(String, CType)]) -> Vec<Param> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < params.len() {
+            let (name, ty) = &params[i];
+            let var = self.param_name(name);
+            let next_is_len = is_len(params, i);
+            let (param, used) = match ty {
+                CType::Ptr { ty, is_const: true } if **ty == CType::Char => {
+                    let utf8 = format!("{}Utf8", var);
+                    let mut args = vec![format!("{}.Get()", utf8)];
+                    if next_is_len {
+                        args.push(format!("{}.Length()", utf8));
+                    }
+                    let param = Param {
+                        decl: format!("const FString& {}", var),
+                        setup: vec![format!("FTCHARToUTF8 {}(*{});", utf8, var)],
+                        args,
+                        kind: ParamKind::String,
+                    };
+                    (param, if next_is_len { 2 } else { 1 })
+                }
+                CType::Ptr { ty, is_const: true } if **ty == CType::UInt8 && next_is_len => {
+                    let param = Param {
+                        decl: format!("const TArray<uint8>& {}", var),
+                        setup: vec![],
+                        args: vec![format!("{}.GetData()", var), format!("{}.Num()", var)],
+                        kind: ParamKind::Bytes,
+                    };
+                    (param, 2)
+                }
+                ty @ CType::Ptr { is_const, .. } if self.handle(ty).is_some() => {
+                    let class = &self.handle(ty).unwrap().class;
+                    let konst = if *is_const { "const " } else { "" };
+                    let param = Param {
+                        decl: format!("{}{}& {}", konst, class, var),
+                        setup: vec![],
+                        args: vec![format!("{}.Get()", var)],
+                        kind: ParamKind::Handle,
+                    };
+                    (param, 1)
+                }
+                ty => (Param { decl: ty.declare(&var), setup: vec![], args: vec![var], kind: ParamKind::Direct(ty.clone()) }, 1),
+            };
+            out.push(param);
+            i += used;
+        }
+        out
+    }
+
+    /// The C++ return type and the statements returning the result of `call`.
+    fn ret(&self, ret: &CType, call: String) -> (String, Vec<String>) {
+        match ret {
+            CType::Void => ("void".to_string(), vec![format!("{};", call)]),
+            CType::Ptr { ty, is_const } if **ty == CType::Char && (*is_const || self.string_free.is_some()) => {
+                let mut body = vec![
+                    format!("{} Utf8Result = {};", ret.declare(""), call),
+                    "FString StringResult = Utf8Result ? FString(UTF8_TO_TCHAR(Utf8Result)) : FString();".to_string(),
+                ];
+                if let (false, Some(free)) = (is_const, self.string_free) {
+                    body.push(format!("{}(Utf8Result);", free.name));
+                }
+                body.push("return StringResult;".to_string());
+                ("FString".to_string(), body)
+            }
+            ty @ CType::Ptr { is_const: false, .. } if self.handle(ty).is_some() => {
+                let class = &self.handle(ty).unwrap().class;
+                (class.clone(), vec![format!("return {}({});", class, call)])
+            }
+            ty => (ty.declare(""), vec![format!("return {};", call)]),
+        }
+    }
+
+    /// `(declaration, definition)` of the wrapper for `f`, named `name` and,
+    /// for methods, qualified with `class`.
+    fn function(&self, f: &CFunction, name: &str, class: Option<&str>, is_method: bool) -> (String, String) {
+        let params = if is_method { &f.params[1..] } else { &f.params[..] };
+        let params = self.params(params);
+        let mut args = if is_method { vec!["Handle".to_string()] } else { vec![] };
+        args.extend(params.iter().flat_map(|p| p.args.iter().cloned()));
+        let (ret, ret_body) = self.ret(&f.ret, format!("{}({})", f.name, args.join(", ")));
+        let decls = params.iter().map(|p| p.decl.as_str()).collect::<Vec<_>>().join(", ");
+        let konst = match f.params.first() {
+            Some((_, CType::Ptr { is_const: true, .. })) if is_method => " const",
+            _ => "",
+        };
+        let qualified = match class {
+            Some(class) => format!("{}::{}", class, name),
+            None => name.to_string(),
+        };
+        let mut def = vec![format!("{} {}({}){} {{", ret, qualified, decls, konst)];
+        for line in params.iter().flat_map(|p| &p.setup).chain(&ret_body) {
+            def.push(format!("    {}", line));
+        }
+        def.push("}".to_string());
+        (format!("{} {}({}){}", ret, name, decls, konst), def.join("\n"))
+    }
+
+    /// `Public/<Module>Rust.h`
+    pub(crate) fn header(&self, module_name: &str) -> Result<String> {
+        let mut out = vec![
+            format!("// UE wrappers over the C API of the Rust library used by the {} module.", module_name),
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+            format!("#include \"{}RustApi.h\"", module_name),
+        ];
+        for handle in &self.handles {
+            let (class, ty) = (&handle.class, handle.ty);
+            out.push(String::new());
+            out.push(format!("/** Owns a `{}`, freed with `{}`. */", ty, handle.free.name));
+            out.push(format!("class {} {} {{", self.api_macro, class));
+            out.push("public:".to_string());
+            for line in [
+                format!("explicit {class}({ty}* InHandle = nullptr) : Handle(InHandle) {{}}"),
+                format!("~{class}() {{ Reset(); }}"),
+                format!("{class}(const {class}&) = delete;"),
+                format!("{class}& operator=(const {class}&) = delete;"),
+                format!("{class}({class}&& Other) : Handle(Other.Release()) {{}}"),
+                format!("{class}& operator=({class}&& Other) {{ if (this != &Other) {{ Reset(); Handle = Other.Release(); }} return *this; }}"),
+                String::new(),
+                "bool IsValid() const { return Handle != nullptr; }".to_string(),
+                format!("{ty}* Get() const {{ return Handle; }}"),
+                format!("{ty}* Release() {{ {ty}* Out = Handle; Handle = nullptr; return Out; }}"),
+                format!("void Reset() {{ if (Handle) {{ {}(Handle); Handle = nullptr; }} }}", handle.free.name),
+            ] {
+                out.push(if line.is_empty() { line } else { format!("    {}", line) });
+            }
+            for (f, name, is_method) in self.members(handle) {
+                if HANDLE_MEMBERS.contains(&name.as_str()) || name == *class {
+                    return Err(Error::UnsupportedType(format!("{}: {}::{} is generated for every handle", f.name, class, name)));
+                }
+                out.push(String::new());
+                push_docs(&mut out, &f.docs, "    ");
+                let (decl, _) = self.function(f, &name, None, is_method);
+                out.push(format!("    {}{};", if is_method { "" } else { "static " }, decl));
+            }
+            out.push(String::new());
+            out.push("private:".to_string());
+            out.push(format!("    {}* Handle;", ty));
+            out.push("};".to_string());
+        }
+        let free = self.free_functions();
+        if !free.is_empty() {
+            out.push(String::new());
+            out.push(format!("namespace {}Rust {{", module_name));
+            for (i, f) in free.iter().enumerate() {
+                if i > 0 {
+                    out.push(String::new());
+                }
+                push_docs(&mut out, &f.docs, "");
+                let (decl, _) = self.function(f, &pascal_case(&f.name), None, false);
+                out.push(format!("{} {};", self.api_macro, decl));
+            }
+            out.push("}".to_string());
+        }
+        out.push(String::new());
+        Ok(out.join("\n"))
+    }
+
+    /// `Private/<Module>Rust.cpp`
+    pub(crate) fn source(&self, module_name: &str) -> String {
+        let mut out = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}Rust.h\"", module_name),
+        ];
+        for handle in &self.handles {
+            for (f, name, is_method) in self.members(handle) {
+                out.push(String::new());
+                out.push(self.function(f, &name, Some(&handle.class), is_method).1);
+            }
+        }
+        for f in self.free_functions() {
+            out.push(String::new());
+            let (_, def) = self.function(f, &pascal_case(&f.name), Some(&format!("{}Rust", module_name)), false);
+            out.push(def);
+        }
+        out.push(String::new());
+        out.join("\n")
+    }
+
+    /// Methods and static constructors of `handle`, with their C++ names.
+    fn members(&self, handle: &Handle) -> Vec<(&'a CFunction, String, bool)> {
+        let prefix = format!("{}_", snake_case(handle.ty));
+        let name = |f: &CFunction| pascal_case(f.name.strip_prefix(&prefix).unwrap_or(&f.name));
+        self.api
+            .functions
+            .iter()
+            .filter(|f| !self.is_free(f))
+            .filter_map(|f| match (self.receiver(f), self.constructed(f)) {
+                (Some(h), _) if h.ty == handle.ty => Some((f, name(f), true)),
+                (None, Some(h)) if h.ty == handle.ty => Some((f, name(f), false)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// `Public/<Module>BlueprintLibrary.h` and `Private/<Module>BlueprintLibrary.cpp`
+    /// with a static `UFUNCTION` per listed function.
+    pub(crate) fn blueprint_library(&self, functions: &[BlueprintFunction], module_name: &str) -> Result<(String, String)> {
+        let class = format!("U{}BlueprintLibrary", module_name);
+        let mut header = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            "#pragma once".to_string(),
+            String::new(),
+            "#include \"CoreMinimal.h\"".to_string(),
+            "#include \"Kismet/BlueprintFunctionLibrary.h\"".to_string(),
+            format!("#include \"{}BlueprintLibrary.generated.h\"", module_name),
+            String::new(),
+            "UCLASS()".to_string(),
+            format!("class {} {} : public UBlueprintFunctionLibrary", self.api_macro, class),
+            "{".to_string(),
+            "    GENERATED_BODY()".to_string(),
+            String::new(),
+            "public:".to_string(),
+        ];
+        let mut source = vec![
+            "// Generated by ueplugingen, do not edit.".to_string(),
+            String::new(),
+            format!("#include \"{}BlueprintLibrary.h\"", module_name),
+            format!("#include \"{}RustApi.h\"", module_name),
+        ];
+        for (i, bp) in functions.iter().enumerate() {
+            let f = self.api
+                .functions
+                .iter()
+                .find(|f| f.name == bp.name)
+                .ok_or_else(|| Error::UnknownFunction(bp.name.to_string()))?;
+            let incompatible = |what: &str| Error::UnsupportedType(format!("{}: {}: not a Blueprint type", f.name, what));
+
+            let mut decls = vec![];
+            let mut setup = vec![];
+            let mut args = vec![];
+            for param in self.params(&f.params) {
+                match &param.kind {
+                    ParamKind::Direct(ty) => {
+                        let bp_type = blueprint_type(ty).ok_or_else(|| incompatible(&param.decl))?;
+                        let var = param.args[0].clone();
+                        decls.push(format!("{} {}", bp_type, var));
+                        args.push(if ty.declare("") == bp_type { var } else { format!("static_cast<{}>({})", ty.declare(""), var) });
+                    }
+                    ParamKind::String | ParamKind::Bytes => {
+                        decls.push(param.decl);
+                        setup.extend(param.setup);
+                        args.extend(param.args);
+                    }
+                    ParamKind::Handle => return Err(incompatible(&param.decl)),
+                }
+            }
+            let call = format!("{}({})", f.name, args.join(", "));
+            let (ret, body) = match &f.ret {
+                ty @ (CType::Void | CType::Ptr { .. }) => {
+                    let (ret, body) = self.ret(ty, call);
+                    if ret != "void" && ret != "FString" {
+                        return Err(incompatible("return"));
+                    }
+                    (ret, body)
+                }
+                ty => {
+                    let bp_type = blueprint_type(ty).ok_or_else(|| incompatible("return"))?;
+                    let call = if ty.declare("") == bp_type { call } else { format!("static_cast<{}>({})", bp_type, call) };
+                    (bp_type.to_string(), vec![format!("return {};", call)])
+                }
+            };
+
+            let name = pascal_case(&f.name);
+            let specifier = if bp.pure { "BlueprintPure" } else { "BlueprintCallable" };
+            if i > 0 {
+                header.push(String::new());
+            }
+            push_docs(&mut header, &f.docs, "    ");
+            header.push(format!("    UFUNCTION({}, Category = \"{}\")", specifier, bp.category.replace('"', "'")));
+            header.push(format!("    static {} {}({});", ret, name, decls.join(", ")));
+
+            source.push(String::new());
+            source.push(format!("{} {}::{}({}) {{", ret, class, name, decls.join(", ")));
+            for line in setup.iter().chain(&body) {
+                source.push(format!("    {}", line));
+            }
+            source.push("}".to_string());
+        }
+        header.push("};".to_string());
+        header.push(String::new());
+        source.push(String::new());
+        Ok((header.join("\n"), source.join("\n")))
+    }
+
+    fn free_functions(&self) -> Vec<&'a CFunction> {
+        self.api
+            .functions
+            .iter()
+            .filter(|f| !self.is_free(f) && self.receiver(f).is_none() && self.constructed(f).is_none())
+            .collect()
+    }
+}
+
+/// The Blueprint type closest to a C scalar. Narrower integers are widened;
+/// unsigned 64-bit ones (`u64`, `usize`, `c_ulong`) are reinterpreted as
+/// `int64`, so values above `i64::MAX` come out negative.
+pub(crate) fn blueprint_type(ty: &CType) -> Option<&'static str> {
+    Some(match ty {
+        CType::Bool => "bool",
+        CType::UInt8 => "uint8",
+        CType::Int8 | CType::Int16 | CType::UInt16 | CType::Int32 => "int32",
+        CType::UInt32 | CType::Long | CType::ULong | CType::Int64 | CType::UInt64 | CType::Size | CType::SSize => "int64",
+        CType::Float => "float",
+        CType::Double => "double",
+        _ => return None,
+    })
+}
+
+/// Whether the parameter after the pointer `params[i]` is its length: an
+/// integer named `len` or `<ptr>_len`.
+pub(crate) fn is_len(params: &[(String, CType)], i: usize) -> bool {
+    let ptr = &params[i].0;
+    params.get(i + 1).is_some_and(|(name, ty)| {
+        matches!(ty, CType::Size | CType::UInt32 | CType::Int32 | CType::UInt64 | CType::Int64)
+            && (name == "len" || name.strip_suffix("_len") == Some(ptr.as_str()))
+    })
+}
+
+/// `on_event` -> `OnEvent`
+pub(crate) fn pascal_case(s: &str) -> String {
+    s.split('_')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let mut chars = p.chars();
+            chars.next().map(|c| c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+        })
+        .collect()
+}
+
+/// `HttpClient` -> `http_client`
+pub(crate) fn snake_case(s: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in s.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.push(c.to_ascii_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, params: &[CType], ret: CType) -> CFunction {
+        let params = params.iter().enumerate().map(|(i, ty)| (format!("arg{}", i), ty.clone())).collect();
+        CFunction { name: name.to_string(), params, ret, docs: vec![] }
+    }
+
+    fn handle_api(ty: &str, method: &str) -> CApi {
+        let ptr = CType::Ptr { ty: Box::new(CType::Named(ty.to_string())), is_const: false };
+        let prefix = snake_case(ty);
+        CApi {
+            functions: vec![
+                function(&format!("{}_new", prefix), &[], ptr.clone()),
+                function(&format!("{}_{}", prefix, method), std::slice::from_ref(&ptr), CType::Bool),
+                function(&format!("{}_free", prefix), &[ptr], CType::Void),
+            ],
+            ..CApi::default()
+        }
+    }
+
+    #[test]
+    fn handle_classes() {
+        let api = handle_api("Client", "is_connected");
+        let wrappers = Wrappers::new(&api, "Foo");
+        let header = wrappers.header("Foo").unwrap();
+        assert!(header.contains("class FOO_API FFooClient {"), "{header}");
+        assert!(header.contains("    explicit FFooClient(Client* InHandle = nullptr) : Handle(InHandle) {}"), "{header}");
+        assert!(header.contains("    static FFooClient New();"), "{header}");
+        assert!(header.contains("    bool IsConnected();"), "{header}");
+        let source = wrappers.source("Foo");
+        assert!(source.contains("FFooClient FFooClient::New() {\n    return FFooClient(client_new());\n}"), "{source}");
+    }
+
+    #[test]
+    fn handle_classes_are_prefixed_with_the_module() {
+        // FVector would redefine the engine's
+        let api = handle_api("Vector", "is_normalized");
+        let header = Wrappers::new(&api, "Foo").header("Foo").unwrap();
+        assert!(header.contains("class FOO_API FFooVector {"), "{header}");
+    }
+
+    #[test]
+    fn methods_named_like_handle_members() {
+        for method in ["reset", "get", "release", "is_valid"] {
+            let api = handle_api("Client", method);
+            assert!(matches!(Wrappers::new(&api, "Foo").header("Foo"), Err(Error::UnsupportedType(_))), "{method}");
+        }
+    }
+
+    #[test]
+    fn length_parameters() {
+        let chars = CType::Ptr { ty: Box::new(CType::Char), is_const: true };
+        let bytes = CType::Ptr { ty: Box::new(CType::UInt8), is_const: true };
+        let f = |name: &str, params: &[(&str, CType)]| {
+            let params = params.iter().map(|(n, ty)| (n.to_string(), ty.clone())).collect();
+            CFunction { name: name.to_string(), params, ret: CType::Void, docs: vec![] }
+        };
+        let api = CApi {
+            functions: vec![
+                f("set_name", &[("name", chars.clone()), ("name_len", CType::Size)]),
+                f("send", &[("data", bytes), ("len", CType::Size)]),
+                f("repeat", &[("name", chars), ("count", CType::UInt32)]),
+            ],
+            ..CApi::default()
+        };
+        let wrappers = Wrappers::new(&api, "Foo");
+        let header = wrappers.header("Foo").unwrap();
+        let source = wrappers.source("Foo");
+        assert!(header.contains("FOO_API void SetName(const FString& Name);"), "{header}");
+        assert!(source.contains("    set_name(NameUtf8.Get(), NameUtf8.Length());"), "{source}");
+        assert!(header.contains("FOO_API void Send(const TArray<uint8>& Data);"), "{header}");
+        assert!(source.contains("    send(Data.GetData(), Data.Num());"), "{source}");
+        // not the length of the string
+        assert!(header.contains("FOO_API void Repeat(const FString& Name, uint32 Count);"), "{header}");
+        assert!(source.contains("    repeat(NameUtf8.Get(), Count);"), "{source}");
+    }
+
+    #[test]
+    fn blueprint_library() {
+        let chars = CType::Ptr { ty: Box::new(CType::Char), is_const: true };
+        let mut score = function("score", &[CType::UInt16, CType::Int64], CType::UInt32);
+        score.params[0].0 = "level".to_string();
+        score.params[1].0 = "bonus".to_string();
+        let mut greet = function("greet", std::slice::from_ref(&chars), chars.clone());
+        greet.params[0].0 = "name".to_string();
+        let api = CApi { functions: vec![score, greet], ..CApi::default() };
+        let functions = [
+            BlueprintFunction { name: "score", category: "Game", pure: true },
+            BlueprintFunction { name: "greet", category: "Game|\"Chat\"", pure: false },
+        ];
+        let (header, source) = Wrappers::new(&api, "Foo").blueprint_library(&functions, "Foo").unwrap();
+        assert!(header.contains("    UFUNCTION(BlueprintPure, Category = \"Game\")\n    static int64 Score(int32 Level, int64 Bonus);"), "{header}");
+        assert!(header.contains("    UFUNCTION(BlueprintCallable, Category = \"Game|'Chat'\")\n    static FString Greet(const FString& Name);"), "{header}");
+        // uint16 widened to int32 and narrowed back, uint32 returned as int64
+        assert!(source.contains("    return static_cast<int64>(score(static_cast<uint16>(Level), Bonus));"), "{source}");
+        assert!(source.contains("    FTCHARToUTF8 NameUtf8(*Name);\n    const ANSICHAR* Utf8Result = greet(NameUtf8.Get());"), "{source}");
+    }
+
+    #[test]
+    fn non_blueprint_functions() {
+        let mut api = handle_api("Client", "is_connected");
+        let floats = CType::Ptr { ty: Box::new(CType::Float), is_const: true };
+        api.functions.push(function("sum", std::slice::from_ref(&floats), CType::Float));
+        api.functions.push(function("samples", &[], floats));
+        api.functions.push(function("initial", &[], CType::Char));
+        for name in ["client_is_connected", "client_new", "sum", "samples", "initial"] {
+            let functions = [BlueprintFunction { name, category: "Foo", pure: false }];
+            let library = Wrappers::new(&api, "Foo").blueprint_library(&functions, "Foo");
+            assert!(matches!(library, Err(Error::UnsupportedType(_))), "{name}");
+        }
+        let functions = [BlueprintFunction { name: "missing", category: "Foo", pure: false }];
+        let library = Wrappers::new(&api, "Foo").blueprint_library(&functions, "Foo");
+        assert!(matches!(library, Err(Error::UnknownFunction(_))));
+    }
+}